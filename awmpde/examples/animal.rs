@@ -17,11 +17,11 @@ pub struct IsAnimalRequest {
     animal_desc: AnimalDesc,
 }
 
-async fn is_animal(req: awmpde::Multipart<IsAnimalRequest>) -> Result<HttpResponse, Error> {
+async fn is_animal(req: awmpde::MultipartForm<IsAnimalRequest>) -> Result<HttpResponse, Error> {
     let IsAnimalRequest {
         imgs,
         animal_desc: AnimalDesc { kind, .. },
-    } = req.into_inner().await?;
+    } = req.into_inner();
     let kind: &str = &kind;
 
     let out = match kind {
@@ -45,8 +45,14 @@ async fn main() -> std::io::Result<()> {
     std::env::set_var("RUST_LOG", "actix_server=info,actix_web=info");
     env_logger::init();
 
-    HttpServer::new(|| {
+    // Limit is shared by all workers
+    let config = awmpde::FieldConfig {
+        image: awmpde::images::ImageConfig::default().max_concurrent(4),
+    };
+
+    HttpServer::new(move || {
         App::new()
+            .app_data(config.clone())
             .wrap(middleware::DefaultHeaders::new().header("X-Version", "0.2"))
             .wrap(middleware::Compress::default())
             .wrap(middleware::Logger::default())
@@ -1,5 +1,5 @@
 use actix_web::{middleware, web, App, HttpResponse, HttpServer};
-use awmpde::{form_or_multipart_unwrap, FromActixMultipart};
+use awmpde::FromActixMultipart;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -8,19 +8,17 @@ pub struct AnimalDesc {
     kind: String,
 }
 
-#[derive(Debug, Deserialize, FromActixMultipart)]
+/// Both for urlencoded and multipart requests `animal_desc` holds json
+#[derive(Debug, FromActixMultipart)]
 pub struct IsAnimalRequest {
     #[serde_json]
     animal_desc: AnimalDesc,
 }
 
-#[form_or_multipart_unwrap]
-async fn is_animal(
-    awmpde::FormOrMultipart(req): awmpde::FormOrMultipart<IsAnimalRequest>,
-) -> HttpResponse {
+async fn is_animal(req: awmpde::FormOrMultipart<IsAnimalRequest>) -> HttpResponse {
     let IsAnimalRequest {
         animal_desc: AnimalDesc { kind, .. },
-    } = req;
+    } = req.into_inner();
     let kind: &str = &kind;
 
     let out = match kind {
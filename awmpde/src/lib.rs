@@ -1,5 +1,6 @@
 // for macro
 pub use actix_multipart;
+pub use actix_web;
 pub use futures;
 
 pub use awmpde_derive::*;
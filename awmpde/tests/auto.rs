@@ -0,0 +1,83 @@
+use actix_web::test::TestRequest;
+use actix_web::ResponseError;
+use awmpde::test::MultipartBuilder;
+use awmpde::{Auto, FromActixMultipart, FromMultipart};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Meta {
+    album: u32,
+    public: bool,
+}
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    count: Auto<u32>,
+    public: Auto<bool>,
+    title: Auto<String>,
+    meta: Vec<Auto<Meta>>,
+}
+
+#[actix_web::test]
+async fn by_content_type() {
+    let req = TestRequest::default().to_http_request();
+    let mp = MultipartBuilder::new()
+        .add_field("count", None, "42".bytes())
+        .add_field("public", Some("text/plain; charset=utf-8"), "true".bytes())
+        .add_field("title", Some("application/json"), r#""cats""#.bytes())
+        .add_field(
+            "meta",
+            Some("application/json"),
+            r#"{"album": 1, "public": false}"#.bytes(),
+        )
+        .add_field(
+            "meta",
+            Some("application/x-www-form-urlencoded"),
+            "album=2&public=true".bytes(),
+        )
+        .build();
+    let up = Upload::from_multipart(&req, mp).await.unwrap();
+
+    assert_eq!(up.count.0, 42);
+    assert!(up.public.0);
+    assert_eq!(up.title.0, "cats");
+    let meta = up.meta.into_iter().map(|m| m.0).collect::<Vec<_>>();
+    assert_eq!(
+        meta,
+        [
+            Meta {
+                album: 1,
+                public: false
+            },
+            Meta {
+                album: 2,
+                public: true
+            },
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn bad_parts() {
+    let req = TestRequest::default().to_http_request();
+    let parts = |count: &'static str, ct| {
+        MultipartBuilder::new()
+            .add_field("count", ct, count.bytes())
+            .add_field("public", None, "false".bytes())
+            .add_field("title", None, "cats".bytes())
+            .build()
+    };
+
+    let err = Upload::from_multipart(&req, parts("many", None))
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, awmpde::Error::ParseError(_)));
+
+    let err = Upload::from_multipart(&req, parts("42", Some("image/png")))
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, awmpde::Error::UnsupportedMediaType(_)));
+    assert_eq!(err.status_code(), 415);
+}
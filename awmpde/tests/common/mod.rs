@@ -0,0 +1,64 @@
+//! Fixtures shared by the integration tests.
+
+// Every test crate uses only some of them
+#![allow(dead_code)]
+
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::TestRequest;
+use actix_web::web::Bytes;
+use awmpde::test::MultipartBuilder;
+use image::{DynamicImage, GenericImageView, ImageEncoder, ImageOutputFormat};
+
+/// Black RGB image of `width`x`height` in `format`.
+pub fn encode(width: u32, height: u32, format: ImageOutputFormat) -> Vec<u8> {
+    let mut buf = Vec::new();
+    DynamicImage::new_rgb8(width, height)
+        .write_to(&mut buf, format)
+        .unwrap();
+    buf
+}
+
+/// Black RGB PNG of `width`x`height`.
+pub fn png(width: u32, height: u32) -> Vec<u8> {
+    encode(width, height, ImageOutputFormat::Png)
+}
+
+/// `img` as PNG.
+pub fn png_of(img: DynamicImage) -> Vec<u8> {
+    // `DynamicImage::write_to` of `image` 0.23 writes 16-bit samples in native byte order
+    let mut buf = Vec::new();
+    let (w, h) = img.dimensions();
+    image::codecs::png::PngEncoder::new(&mut buf)
+        .write_image(img.as_bytes(), w, h, img.color())
+        .unwrap();
+    buf
+}
+
+/// CRC of PNG chunks.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+    !crc
+}
+
+/// Multipart of files, each given by field name, content type and content.
+pub fn files(fields: Vec<(&'static str, &'static str, Vec<u8>)>) -> MultipartBuilder {
+    fields
+        .into_iter()
+        .fold(MultipartBuilder::new(), |mp, (name, ct, content)| {
+            mp.add_file(name, "img", Some(ct), content)
+        })
+}
+
+/// Post of multipart `body` to `uri`.
+pub fn post(uri: &str, body: Bytes) -> TestRequest {
+    TestRequest::post()
+        .uri(uri)
+        .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+        .set_payload(body)
+}
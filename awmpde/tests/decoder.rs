@@ -0,0 +1,114 @@
+mod common;
+
+use actix_web::test::{call_service, init_service, read_body};
+use actix_web::{post, App, HttpResponse};
+use awmpde::images::{DecodeHint, ImageConfig, ImageDecoder, ImageFormat, ImageRs, RgbImage};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FieldConfig, FromActixMultipart, MultipartForm};
+use common::encode;
+use image::{DynamicImage, ImageOutputFormat, ImageResult};
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    #[awmpde(decoder = "image")]
+    photo: RgbImage,
+    scan: RgbImage,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{:?} {:?}",
+        upload.photo.dimensions(),
+        upload.scan.dimensions()
+    ))
+}
+
+/// Backend decoding only JPEGs, every one of them to a blank 7x7 image.
+struct JpegOnly;
+
+impl ImageDecoder for JpegOnly {
+    fn decodes(&self, format: ImageFormat) -> bool {
+        format == ImageFormat::Jpeg
+    }
+
+    fn decode(&self, _buf: &[u8], _: ImageFormat, _: DecodeHint) -> ImageResult<DynamicImage> {
+        Ok(DynamicImage::new_rgb8(7, 7))
+    }
+}
+
+fn config() -> FieldConfig {
+    FieldConfig {
+        image: ImageConfig::default().decoder(JpegOnly),
+    }
+}
+
+/// Adobe CMYK JPEG of 8x8 pixels, all stored as (255, 128, 0, 255).
+const CMYK: &[u8] = include_bytes!("fixtures/cmyk.jpg");
+
+#[actix_web::test]
+async fn fallback() {
+    let app = init_service(App::new().app_data(config()).service(upload)).await;
+    let png = encode(5, 3, ImageOutputFormat::Png);
+    let jpeg = encode(4, 2, ImageOutputFormat::Jpeg(80));
+
+    // PNGs go to `ImageRs`, as do all images of a field with `decoder = "image"`
+    let requests = [
+        ((&jpeg, "image/jpeg"), (&png, "image/png"), "(4, 2) (5, 3)"),
+        ((&png, "image/png"), (&jpeg, "image/jpeg"), "(5, 3) (7, 7)"),
+    ];
+    for ((photo, photo_ct), (scan, scan_ct), expected) in requests.iter() {
+        let body = MultipartBuilder::new()
+            .add_file("photo", "photo", Some(photo_ct), photo.to_vec())
+            .add_file("scan", "scan", Some(scan_ct), scan.to_vec())
+            .build_payload_bytes();
+        let resp = call_service(&app, common::post("/upload", body).to_request()).await;
+        assert_eq!(resp.status().as_u16(), 200);
+        assert_eq!(read_body(resp).await, expected.as_bytes());
+    }
+}
+
+/// `image` reads Adobe CMYK as inverted, like the default of `Mozjpeg`.
+#[test]
+fn cmyk_image_rs() {
+    let hint = DecodeHint {
+        color: None,
+        scale: 8,
+    };
+    let rgb = ImageRs
+        .decode(CMYK, ImageFormat::Jpeg, hint)
+        .unwrap()
+        .into_rgb8();
+    assert_eq!(rgb.dimensions(), (8, 8));
+    assert!(rgb.pixels().all(|p| p.0 == [255, 128, 0]));
+}
+
+#[cfg(feature = "mozjpeg")]
+#[test]
+fn cmyk() {
+    use awmpde::images::{CmykMode, DctMethod, Mozjpeg};
+
+    let hint = DecodeHint {
+        color: None,
+        scale: 8,
+    };
+    let decode = |cmyk| {
+        let decoder = Mozjpeg {
+            dct_method: DctMethod::IntegerSlow,
+            cmyk,
+            ..Mozjpeg::default()
+        };
+        decoder.decode(CMYK, ImageFormat::Jpeg, hint)
+    };
+
+    // Inverted CMYK stores ink as 255 - value, so the color is orange
+    let rgb = decode(CmykMode::Inverted).unwrap().into_rgb8();
+    assert_eq!(rgb.dimensions(), (8, 8));
+    assert!(rgb.pixels().all(|p| p.0 == [255, 128, 0]));
+
+    // Full black ink otherwise
+    let rgb = decode(CmykMode::Plain).unwrap().into_rgb8();
+    assert!(rgb.pixels().all(|p| p.0 == [0, 0, 0]));
+
+    assert!(decode(CmykMode::Reject).is_err());
+}
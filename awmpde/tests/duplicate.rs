@@ -0,0 +1,39 @@
+use actix_web::test::TestRequest;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart};
+
+#[derive(FromActixMultipart)]
+struct Help {
+    user_id: String,
+    #[awmpde(duplicate = "first")]
+    tag: Option<String>,
+    #[awmpde(duplicate = "last")]
+    note: String,
+}
+
+#[actix_web::test]
+async fn policies() {
+    let req = TestRequest::default().to_http_request();
+    let mp = MultipartBuilder::new()
+        .add_field("user_id", None, "1".bytes())
+        .add_field("tag", None, "cat".bytes())
+        .add_field("tag", None, "dog".bytes())
+        .add_field("note", None, "old".bytes())
+        .add_field("note", None, "new".bytes())
+        .build();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+
+    assert_eq!(help.user_id, "1");
+    assert_eq!(help.tag.as_deref(), Some("cat"));
+    assert_eq!(help.note, "new");
+
+    let mp = MultipartBuilder::new()
+        .add_field("user_id", None, "1".bytes())
+        .add_field("user_id", None, "2".bytes())
+        .add_field("note", None, "".bytes())
+        .build();
+    assert!(matches!(
+        Help::from_multipart(&req, mp).await,
+        Err(awmpde::Error::DuplicateFieldError("user_id"))
+    ));
+}
@@ -0,0 +1,118 @@
+#![cfg(feature = "exif")]
+
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::exif_field::Exif;
+use awmpde::images::{Probed, RgbImage};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart, MultipartForm};
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    #[awmpde(auto_orient)]
+    photo: RgbImage,
+    #[awmpde(strip_gps)]
+    original: Probed,
+    #[awmpde(strip_gps)]
+    meta: Exif,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{:?} {} {:?}",
+        upload.photo.dimensions(),
+        upload.original.inner.len(),
+        upload.meta.model
+    ))
+}
+
+#[test]
+fn no_exif() {
+    let exif = Exif::from_bytes(b"\x89PNG\r\n\x1a\n").unwrap();
+    assert!(exif.fields.is_empty());
+    assert_eq!(exif.gps, None);
+}
+
+/// JPEG with EXIF of camera model `Cam` taken at 52.5 N, 13.4 W.
+fn jpeg_with_gps() -> Vec<u8> {
+    fn entry(tag: u16, kind: u16, count: u32, value: [u8; 4]) -> Vec<u8> {
+        let mut entry = tag.to_le_bytes().to_vec();
+        entry.extend_from_slice(&kind.to_le_bytes());
+        entry.extend_from_slice(&count.to_le_bytes());
+        entry.extend_from_slice(&value);
+        entry
+    }
+    fn rationals(values: [u32; 3]) -> Vec<u8> {
+        let pairs = values.iter().flat_map(|v| vec![*v, 1]);
+        pairs.flat_map(|v| v.to_le_bytes().to_vec()).collect()
+    }
+
+    // Header, IFD0 at 8, GPS IFD at 38, coordinates at 92 and 116
+    let mut tiff = b"II\x2a\x00\x08\x00\x00\x00\x02\x00".to_vec();
+    tiff.extend(entry(0x0110, 2, 4, *b"Cam\0"));
+    tiff.extend(entry(0x8825, 4, 1, 38u32.to_le_bytes()));
+    tiff.extend_from_slice(&[0; 4]);
+    tiff.extend_from_slice(&4u16.to_le_bytes());
+    tiff.extend(entry(0x0001, 2, 2, *b"N\0\0\0"));
+    tiff.extend(entry(0x0002, 5, 3, 92u32.to_le_bytes()));
+    tiff.extend(entry(0x0003, 2, 2, *b"W\0\0\0"));
+    tiff.extend(entry(0x0004, 5, 3, 116u32.to_le_bytes()));
+    tiff.extend_from_slice(&[0; 4]);
+    tiff.extend(rationals([52, 30, 0]));
+    tiff.extend(rationals([13, 24, 0]));
+
+    let mut jpeg = Vec::new();
+    image::DynamicImage::new_rgb8(4, 2)
+        .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(80))
+        .unwrap();
+    let mut app1 = vec![0xff, 0xe1];
+    app1.extend_from_slice(&(8 + tiff.len() as u16).to_be_bytes());
+    app1.extend_from_slice(b"Exif\0\0");
+    app1.extend(tiff);
+    jpeg.splice(2..2, app1);
+    jpeg
+}
+
+#[actix_web::test]
+async fn strip_gps() {
+    let jpeg = jpeg_with_gps();
+    let exif = Exif::from_bytes(&jpeg).unwrap();
+    assert_eq!(exif.model.as_deref(), Some("Cam"));
+    let gps = exif.gps.unwrap();
+    assert!((gps.latitude - 52.5).abs() < 1e-9 && (gps.longitude + 13.4).abs() < 1e-9);
+
+    let body = MultipartBuilder::new()
+        .add_file("photo", "a.jpg", Some("image/jpeg"), jpeg.clone())
+        .add_file("original", "a.jpg", Some("image/jpeg"), jpeg.clone())
+        .add_file("meta", "a.jpg", Some("image/jpeg"), jpeg.clone())
+        .build_payload_bytes();
+
+    let app = init_service(App::new().service(upload)).await;
+    let req = TestRequest::post()
+        .uri("/upload")
+        .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+        .set_payload(body.clone())
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    let expected = format!("(4, 2) {} Some(\"Cam\")", jpeg.len());
+    assert_eq!(read_body(resp).await, expected.as_bytes());
+
+    let req = TestRequest::default()
+        .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+        .to_http_request();
+    let mp = awmpde::actix_multipart::Multipart::new(
+        req.headers(),
+        awmpde::futures::stream::once(async move { Ok(body) }),
+    );
+    let parsed = Upload::from_multipart(&req, mp).await.unwrap();
+    assert_eq!(parsed.meta.gps, None);
+    assert_eq!(parsed.meta.model.as_deref(), Some("Cam"));
+    // Kept bytes have the GPS tags blanked in place, the rest of EXIF is untouched
+    let kept = Exif::from_bytes(&parsed.original.inner).unwrap();
+    assert_eq!(kept.gps, None);
+    assert_eq!(kept.model.as_deref(), Some("Cam"));
+    assert_eq!(parsed.original.inner.len(), jpeg.len());
+}
@@ -0,0 +1,42 @@
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{error, post, web, App, HttpResponse};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, MultipartForm, MultipartFormConfig};
+
+#[derive(FromActixMultipart)]
+struct Help {
+    _img: awmpde::File<Vec<u8>>,
+    animal: String,
+}
+
+#[post("/test")]
+async fn test(help: MultipartForm<Help>) -> web::Json<String> {
+    web::Json(help.animal.clone())
+}
+
+#[actix_web::test]
+async fn config() {
+    let cfg = MultipartFormConfig::default().error_handler(|e, _req| {
+        error::InternalError::from_response(e, HttpResponse::Conflict().finish()).into()
+    });
+    let app = init_service(App::new().app_data(cfg).service(test)).await;
+    let request = |body: MultipartBuilder| {
+        TestRequest::post()
+            .uri("/test")
+            .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+            .set_payload(body.build_payload_bytes())
+            .to_request()
+    };
+
+    let body = MultipartBuilder::new()
+        .add_file("_img", "cat.png", Some("image/png"), *b"\x89PNG")
+        .add_field("animal", None, "cat".bytes());
+    let resp = call_service(&app, request(body)).await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(read_body(resp).await, r#""cat""#);
+
+    // Missing `animal` goes through the error handler
+    let body = MultipartBuilder::new().add_file("_img", "cat.png", None, *b"\x89PNG");
+    assert_eq!(call_service(&app, request(body)).await.status(), 409);
+}
@@ -0,0 +1,152 @@
+// Still checks the deprecated `form_or_multipart_unwrap` shim
+#![allow(deprecated)]
+
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, web, App, HttpRequest, HttpResponse};
+use awmpde::test::MultipartBuilder;
+use awmpde::{Auto, FormOrMultipart, FromActixMultipart, Json};
+use serde::Deserialize;
+
+#[derive(FromActixMultipart)]
+struct Help {
+    animal: String,
+}
+
+#[post("/test/{id}")]
+async fn test(
+    _req: HttpRequest,
+    FormOrMultipart(help): FormOrMultipart<Help>,
+    id: web::Path<u32>,
+) -> HttpResponse {
+    HttpResponse::Ok().body(format!("{} {}", id, help.animal))
+}
+
+#[derive(Deserialize)]
+struct Pet {
+    name: String,
+}
+
+#[derive(FromActixMultipart)]
+struct Adopt {
+    pet: Auto<Pet>,
+    age: Auto<u32>,
+    ids: Vec<u32>,
+    tags: Option<Json<Vec<String>>>,
+}
+
+#[post("/adopt")]
+async fn adopt(FormOrMultipart(adopt): FormOrMultipart<Adopt>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{} {} {:?} {:?}",
+        adopt.pet.name,
+        *adopt.age,
+        adopt.ids,
+        adopt.tags.map(|tags| tags.0)
+    ))
+}
+
+#[awmpde::form_or_multipart_unwrap]
+#[post("/legacy")]
+async fn legacy(help: FormOrMultipart<Help>) -> HttpResponse {
+    HttpResponse::Ok().body(help.into_inner().animal)
+}
+
+#[actix_web::test]
+async fn content_types() {
+    let app = init_service(App::new().service(test).service(legacy)).await;
+    let multipart = MultipartBuilder::new()
+        .add_field("animal", None, "cat".bytes())
+        .build_payload_bytes();
+
+    let requests: &[(Option<&str>, &[u8], u16, &str)] = &[
+        (
+            Some("application/x-www-form-urlencoded; charset=utf-8"),
+            b"animal=dog",
+            200,
+            "1 dog",
+        ),
+        (
+            Some(MultipartBuilder::CONTENT_TYPE),
+            &multipart,
+            200,
+            "1 cat",
+        ),
+        (
+            Some("application/json"),
+            br#"{"animal": "owl"}"#,
+            200,
+            "1 owl",
+        ),
+        (
+            Some("application/vnd.animal+json"),
+            br#"{"animal": "fox"}"#,
+            200,
+            "1 fox",
+        ),
+        (Some("text/plain"), b"animal=cow\r\n", 200, "1 cow"),
+        (None, b"animal=pig", 415, ""),
+        (Some("image/png"), b"animal=pig", 415, ""),
+    ];
+
+    for &(ct, body, status, out) in requests {
+        let mut req = TestRequest::post()
+            .uri("/test/1")
+            .set_payload(body.to_vec());
+        if let Some(ct) = ct {
+            req = req.insert_header((CONTENT_TYPE, ct));
+        }
+        let resp = call_service(&app, req.to_request()).await;
+
+        assert_eq!(resp.status(), status, "{:?}", ct);
+        if status == 200 {
+            assert_eq!(read_body(resp).await, out);
+        }
+    }
+
+    let req = TestRequest::post()
+        .uri("/legacy")
+        .insert_header((CONTENT_TYPE, "application/x-www-form-urlencoded"))
+        .set_payload("animal=dog")
+        .to_request();
+    assert_eq!(read_body(call_service(&app, req).await).await, "dog");
+}
+
+#[actix_web::test]
+async fn json_values() {
+    let app = init_service(App::new().service(adopt)).await;
+
+    let requests: &[(&str, u16, &str)] = &[
+        // Objects and numbers stay json, arrays fill `Vec` fields
+        (
+            r#"{"pet": {"name": "Rex"}, "age": 3, "ids": [1, 2]}"#,
+            200,
+            "Rex 3 [1, 2] None",
+        ),
+        (
+            r#"{"pet": {"name": "Tom"}, "age": 1, "ids": 7}"#,
+            200,
+            "Tom 1 [7] None",
+        ),
+        // A json array can't fill a single `Json<Vec<T>>` field
+        (
+            r#"{"pet": {"name": "Rex"}, "age": 3, "ids": [], "tags": ["a", "b"]}"#,
+            400,
+            "",
+        ),
+    ];
+
+    for &(body, status, out) in requests {
+        let req = TestRequest::post()
+            .uri("/adopt")
+            .insert_header((CONTENT_TYPE, "application/json"))
+            .set_payload(body)
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(resp.status(), status, "{}", body);
+        if status == 200 {
+            assert_eq!(read_body(resp).await, out);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use actix_web::test::TestRequest;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Desc {
+    name: String,
+}
+
+#[derive(FromActixMultipart)]
+struct Help {
+    #[serde_json]
+    desc: Option<Desc>,
+    #[awmpde(format = "json")]
+    descs: Vec<Desc>,
+    all: awmpde::Json<Vec<Desc>>,
+}
+
+fn desc(name: &str) -> Desc {
+    Desc {
+        name: name.to_string(),
+    }
+}
+
+#[actix_web::test]
+async fn option_and_vec() {
+    let mp = MultipartBuilder::new()
+        .add_field("desc", None, r#"{"name": "cat"}"#.bytes())
+        .add_field("descs", None, r#"{"name": "dog"}"#.bytes())
+        .add_field("descs", None, r#"{"name": "fox"}"#.bytes())
+        .add_field("all", None, r#"[{"name": "owl"}]"#.bytes())
+        .build();
+    let req = TestRequest::default().to_http_request();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+
+    assert_eq!(help.desc, Some(desc("cat")));
+    assert_eq!(help.descs, [desc("dog"), desc("fox")]);
+    assert_eq!(help.all.0, [desc("owl")]);
+
+    let mp = MultipartBuilder::new()
+        .add_field("all", None, "[]".bytes())
+        .build();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+    assert_eq!(help.desc, None);
+    assert!(help.descs.is_empty());
+
+    let mp = MultipartBuilder::new()
+        .add_field("desc", None, "{}".bytes())
+        .add_field("all", None, "[]".bytes())
+        .build();
+    assert!(Help::from_multipart(&req, mp).await.is_err());
+}
+
+/// Test of a `format` for `Option` and `Vec` fields, with `encode` giving a `Desc` in it.
+macro_rules! format_test(
+    { $feature:tt, $test:ident, $ct:expr, $encode:expr } => {
+        #[cfg(feature = $feature)]
+        #[actix_web::test]
+        async fn $test() {
+            #[derive(FromActixMultipart)]
+            struct Help {
+                #[awmpde(format = $feature)]
+                desc: Option<Desc>,
+                #[awmpde(format = $feature)]
+                descs: Vec<Desc>,
+            }
+
+            let encode: fn(&str) -> Vec<u8> = $encode;
+            let req = TestRequest::default().to_http_request();
+            let mp = MultipartBuilder::new()
+                .add_field("desc", Some($ct), encode("cat"))
+                .add_field("descs", Some($ct), encode("dog"))
+                .add_field("descs", Some($ct), encode("fox"))
+                .build();
+            let help = Help::from_multipart(&req, mp).await.unwrap();
+            assert_eq!(help.desc, Some(desc("cat")));
+            assert_eq!(help.descs, [desc("dog"), desc("fox")]);
+
+            let mp = MultipartBuilder::new().build();
+            let help = Help::from_multipart(&req, mp).await.unwrap();
+            assert_eq!(help.desc, None);
+            assert!(help.descs.is_empty());
+
+            let mp = MultipartBuilder::new()
+                .add_field("descs", Some($ct), encode("owl"))
+                .add_field("descs", Some($ct), vec![0xff, 0x00])
+                .build();
+            assert!(Help::from_multipart(&req, mp).await.is_err());
+        }
+    };
+);
+
+format_test!("toml", toml, "application/toml", |name| {
+    format!("name = \"{}\"", name).into_bytes()
+});
+format_test!("yaml", yaml, "application/yaml", |name| {
+    format!("name: {}", name).into_bytes()
+});
+format_test!("xml", xml, "application/xml", |name| {
+    format!("<desc><name>{}</name></desc>", name).into_bytes()
+});
+// Map of one string to a string
+format_test!("msgpack", msgpack, "application/msgpack", |name| {
+    let mut buf = b"\x81\xa4name".to_vec();
+    buf.push(0xa0 | name.len() as u8);
+    buf.extend_from_slice(name.as_bytes());
+    buf
+});
+format_test!("cbor", cbor, "application/cbor", |name| {
+    let mut buf = b"\xa1\x64name".to_vec();
+    buf.push(0x60 | name.len() as u8);
+    buf.extend_from_slice(name.as_bytes());
+    buf
+});
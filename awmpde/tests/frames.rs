@@ -0,0 +1,286 @@
+mod common;
+
+use std::time::Duration;
+
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{post, App, HttpResponse, ResponseError};
+use awmpde::images::{Disposal, Frames, ImageInfo, RgbImage, Rgba};
+use awmpde::{FromActixMultipart, FromMultipart, MultipartForm};
+use common::files;
+use image::{DynamicImage, RgbaImage};
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    #[awmpde(max_frames = 100, max_total_pixels = 50_000_000)]
+    sticker: Frames,
+    #[awmpde(reject_animated)]
+    avatar: RgbImage,
+    #[awmpde(reject_animated)]
+    banner: ImageInfo,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    let delays = upload
+        .sticker
+        .iter()
+        .map(|frame| frame.delay)
+        .collect::<Vec<_>>();
+    HttpResponse::Ok().body(format!(
+        "{:?} {:?} {}",
+        delays,
+        upload.avatar.dimensions(),
+        upload.banner.width
+    ))
+}
+
+#[derive(FromActixMultipart)]
+struct Limited {
+    #[awmpde(max_frames = 2)]
+    few: Option<Frames>,
+    #[awmpde(max_total_pixels = 12)]
+    small: Option<Frames>,
+}
+
+const GIF_DISPOSALS: [Disposal; 3] = [Disposal::Background, Disposal::Previous, Disposal::Keep];
+const GIF_DELAYS: [u64; 3] = [100, 250, 40];
+
+/// Three 3x2 frames with `GIF_DISPOSALS` and `GIF_DELAYS`, looping if `repeat`.
+fn gif(repeat: bool) -> Vec<u8> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    use image::{Delay, Frame};
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut buf);
+        if repeat {
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+        }
+        for (i, delay) in GIF_DELAYS.iter().enumerate() {
+            let img = RgbaImage::from_pixel(3, 2, Rgba([i as u8 * 100, 0, 0, 255]));
+            let delay = Delay::from_numer_denom_ms(*delay as u32, 1);
+            encoder
+                .encode_frame(Frame::from_parts(img, 0, 0, delay))
+                .unwrap();
+        }
+    }
+
+    // Disposal methods of the graphic control extensions, which `image` leaves unset
+    let mut gces = Vec::new();
+    for (pos, window) in buf.windows(3).enumerate() {
+        if window == [0x21, 0xf9, 0x04] {
+            gces.push(pos + 3);
+        }
+    }
+    assert_eq!(gces.len(), 3);
+    for (pos, disposal) in gces.into_iter().zip(GIF_DISPOSALS.iter()) {
+        let method = match disposal {
+            Disposal::Keep => 1,
+            Disposal::Background => 2,
+            Disposal::Previous => 3,
+        };
+        buf[pos] = (buf[pos] & !0x1c) | (method << 2);
+    }
+    buf
+}
+
+fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut out = (data.len() as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = common::crc32(&out[4..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+    out
+}
+
+/// Compressed image data of a 2x2 PNG of `color`.
+fn idat(color: [u8; 4]) -> Vec<u8> {
+    let png = common::png_of(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        2,
+        2,
+        Rgba(color),
+    )));
+    let pos = png.windows(4).position(|w| w == b"IDAT").unwrap();
+    let len = u32::from_be_bytes([png[pos - 4], png[pos - 3], png[pos - 2], png[pos - 1]]);
+    png[pos + 4..pos + 4 + len as usize].to_vec()
+}
+
+/// Two 2x2 frames, shown for 100 ms and cleared, then for 150 ms and kept.
+fn apng() -> Vec<u8> {
+    let fctl = |seq: u32, (num, den): (u16, u16), dispose: u8| {
+        let mut data = seq.to_be_bytes().to_vec();
+        for v in [2u32, 2, 0, 0].iter() {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        data.extend_from_slice(&num.to_be_bytes());
+        data.extend_from_slice(&den.to_be_bytes());
+        data.extend_from_slice(&[dispose, 0]);
+        chunk(b"fcTL", &data)
+    };
+
+    let mut ihdr = Vec::new();
+    ihdr.extend_from_slice(&2u32.to_be_bytes());
+    ihdr.extend_from_slice(&2u32.to_be_bytes());
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+    let mut fdat = 2u32.to_be_bytes().to_vec();
+    fdat.extend(idat([0, 0, 255, 255]));
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png.extend(chunk(b"IHDR", &ihdr));
+    png.extend(chunk(b"acTL", &[0, 0, 0, 2, 0, 0, 0, 0]));
+    png.extend(fctl(0, (1, 10), 1));
+    png.extend(chunk(b"IDAT", &idat([255, 0, 0, 255])));
+    png.extend(fctl(1, (3, 20), 0));
+    png.extend(chunk(b"fdAT", &fdat));
+    png.extend(chunk(b"IEND", &[]));
+    png
+}
+
+#[actix_web::test]
+async fn animations() {
+    let req = TestRequest::default().to_http_request();
+    for repeat in [true, false].iter() {
+        let mp = files(vec![
+            ("sticker", "image/gif", gif(*repeat)),
+            ("avatar", "image/png", apng_still()),
+            ("banner", "image/png", apng_still()),
+        ]);
+        let parsed = Upload::from_multipart(&req, mp.build()).await.unwrap();
+        let frames = parsed.sticker.into_inner();
+        assert_eq!(frames.len(), 3);
+        for ((frame, disposal), delay) in frames.iter().zip(&GIF_DISPOSALS).zip(&GIF_DELAYS) {
+            assert_eq!(frame.disposal, Some(*disposal));
+            assert_eq!(frame.delay, Duration::from_millis(*delay));
+            assert_eq!(frame.buffer.dimensions(), (3, 2));
+        }
+    }
+
+    let mp = files(vec![
+        ("sticker", "image/apng", apng()),
+        ("avatar", "image/png", apng_still()),
+        ("banner", "image/png", apng_still()),
+    ]);
+    let parsed = Upload::from_multipart(&req, mp.build()).await.unwrap();
+    let frames = parsed.sticker.into_inner();
+    assert_eq!(frames.len(), 2);
+    let expected = [(Disposal::Background, 100), (Disposal::Keep, 150)];
+    for (frame, (disposal, delay)) in frames.iter().zip(&expected) {
+        assert_eq!(frame.disposal, Some(*disposal));
+        assert_eq!(frame.delay, Duration::from_millis(*delay));
+    }
+    assert_eq!(frames[0].buffer.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(frames[1].buffer.get_pixel(1, 1).0, [0, 0, 255, 255]);
+
+    // Still images can't be animated, even GIFs without a loop extension
+    let stills = [
+        ("avatar", "image/png", apng()),
+        ("avatar", "image/gif", gif(false)),
+        ("banner", "image/gif", gif(false)),
+    ];
+    for (name, ct, img) in stills.iter() {
+        let mut fields = vec![
+            ("sticker", "image/gif", gif(true)),
+            ("avatar", "image/png", apng_still()),
+            ("banner", "image/png", apng_still()),
+        ];
+        fields.retain(|(field, ..)| field != name);
+        fields.push((name, ct, img.clone()));
+        let res = Upload::from_multipart(&req, files(fields).build()).await;
+        assert!(
+            matches!(res, Err(awmpde::Error::ImageAnimated)),
+            "{} {}",
+            name,
+            ct
+        );
+    }
+}
+
+/// First frame of `apng` as a still PNG.
+fn apng_still() -> Vec<u8> {
+    let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+    common::png_of(DynamicImage::ImageRgba8(red))
+}
+
+#[actix_web::test]
+async fn limits() {
+    let req = TestRequest::default().to_http_request();
+    let requests = vec![
+        (vec![("few", "image/apng", apng())], true),
+        (vec![("few", "image/gif", gif(true))], false),
+        (vec![("small", "image/apng", apng())], true),
+        (vec![("small", "image/gif", gif(true))], false),
+    ];
+
+    for (i, (fields, ok)) in requests.into_iter().enumerate() {
+        let res = Limited::from_multipart(&req, files(fields.clone()).build()).await;
+        match res {
+            Ok(_) => assert!(ok, "request {}", i),
+            Err(e) => {
+                assert!(!ok, "request {}", i);
+                assert!(
+                    matches!(e, awmpde::Error::AnimationTooLarge),
+                    "request {}",
+                    i
+                );
+                assert_eq!(e.status_code().as_u16(), 413);
+            }
+        }
+    }
+
+    let app = init_service(App::new().service(limited)).await;
+    let body = files(vec![("few", "image/gif", gif(true))]).build_payload_bytes();
+    let resp = call_service(&app, common::post("/limited", body).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 413);
+}
+
+#[post("/limited")]
+async fn limited(MultipartForm(limited): MultipartForm<Limited>) -> HttpResponse {
+    let count = |frames: Option<Frames>| frames.map_or(0, |frames| frames.len());
+    HttpResponse::Ok().body(format!("{} {}", count(limited.few), count(limited.small)))
+}
+
+/// Header of a 2x2 animated WebP with one empty frame, `image` 0.23 can't decode it.
+fn animated_webp() -> Vec<u8> {
+    let chunk = |kind: &[u8; 4], data: &[u8]| {
+        let mut out = kind.to_vec();
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+        out
+    };
+
+    // Animation flag, then canvas width and height minus one
+    let vp8x = [0x02, 0, 0, 0, 1, 0, 0, 1, 0, 0];
+    // Frame offset, size minus one, duration and flags
+    let anmf = [0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 0, 0, 100, 0, 0, 0];
+    let mut body = b"WEBP".to_vec();
+    body.extend(chunk(b"VP8X", &vp8x));
+    body.extend(chunk(b"ANIM", &[0, 0, 0, 0, 0, 0]));
+    body.extend(chunk(b"ANMF", &anmf));
+
+    let mut webp = b"RIFF".to_vec();
+    webp.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    webp.extend(body);
+    webp
+}
+
+#[actix_web::test]
+async fn webp_unsupported() {
+    let req = TestRequest::default().to_http_request();
+    let mp = files(vec![("few", "image/webp", animated_webp())]);
+    let res = Limited::from_multipart(&req, mp.build()).await;
+    assert!(
+        matches!(
+            res,
+            Err(awmpde::Error::ImageDecodeError(
+                image::ImageError::Unsupported(_)
+            ))
+        ),
+        "{:?}",
+        res.err()
+    );
+
+    let app = init_service(App::new().service(limited)).await;
+    let body = files(vec![("few", "image/webp", animated_webp())]).build_payload_bytes();
+    let resp = call_service(&app, common::post("/limited", body).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 400);
+}
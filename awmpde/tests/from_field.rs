@@ -0,0 +1,49 @@
+use actix_web::test::TestRequest;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromField, FromMultipart};
+
+#[derive(FromField)]
+struct Title(String);
+
+#[derive(FromField)]
+struct Wrapped<T>(T);
+
+#[derive(FromField)]
+struct Nickname {
+    inner: String,
+}
+
+#[derive(FromField, Debug, PartialEq)]
+#[awmpde(rename_all = "snake_case", case_insensitive)]
+enum Kind {
+    Dog,
+    Cat,
+    #[awmpde(rename = "guinea-pig")]
+    GuineaPig,
+}
+
+#[derive(FromActixMultipart)]
+struct Help {
+    title: Title,
+    nickname: Option<Nickname>,
+    motto: Wrapped<String>,
+    kinds: Vec<Kind>,
+}
+
+#[actix_web::test]
+async fn derived() {
+    let mp = MultipartBuilder::new()
+        .add_field("title", None, "Dr".bytes())
+        .add_field("nickname", None, "Rex".bytes())
+        .add_field("motto", None, "woof".bytes())
+        .add_field("kinds", None, "DOG".bytes())
+        .add_field("kinds", None, "guinea-pig".bytes())
+        .build();
+    let req = TestRequest::default().to_http_request();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+
+    assert_eq!(help.title.0, "Dr");
+    assert_eq!(help.nickname.unwrap().inner, "Rex");
+    assert_eq!(help.motto.0, "woof");
+    assert_eq!(help.kinds, [Kind::Dog, Kind::GuineaPig]);
+}
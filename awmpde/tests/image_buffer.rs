@@ -0,0 +1,69 @@
+mod common;
+
+use actix_web::test::TestRequest;
+use actix_web::{post, HttpResponse};
+use awmpde::images::{ImageBuffer, Luma, Rgb, Rgba};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart, MultipartForm};
+use common::png_of as png;
+
+#[derive(FromActixMultipart)]
+struct Scan {
+    depth: ImageBuffer<Luma<u16>, Vec<u16>>,
+    color: ImageBuffer<Rgb<u16>, Vec<u16>>,
+    hdr: ImageBuffer<Rgba<f32>, Vec<f32>>,
+    preview: Option<ImageBuffer<Rgb<u8>, Vec<u8>>>,
+}
+
+#[post("/scan")]
+async fn scan(MultipartForm(scan): MultipartForm<Scan>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{:?} {:?} {:?} {}",
+        scan.depth.dimensions(),
+        scan.color.dimensions(),
+        scan.hdr.dimensions(),
+        scan.preview.is_some()
+    ))
+}
+
+#[derive(FromActixMultipart)]
+struct Depth {
+    depth: ImageBuffer<Luma<u16>, Vec<u16>>,
+    color: ImageBuffer<Rgb<u16>, Vec<u16>>,
+    hdr: ImageBuffer<Rgba<f32>, Vec<f32>>,
+    hdr8: ImageBuffer<Rgba<f32>, Vec<f32>>,
+}
+
+#[actix_web::test]
+async fn depth() {
+    use image::DynamicImage::{ImageLuma16, ImageRgb16, ImageRgba16, ImageRgba8};
+
+    let depth = image::ImageBuffer::from_pixel(3, 2, Luma([0x1234u16]));
+    let color = image::ImageBuffer::from_pixel(1, 1, Rgb([1u16, 0x8000, 0xfffe]));
+    let hdr = image::ImageBuffer::from_pixel(2, 2, Rgba([0u16, 0x8000, 0xffff, 0x1234]));
+    let preview = image::ImageBuffer::from_pixel(1, 1, Rgba([255u8, 128, 0, 255]));
+
+    let mp = MultipartBuilder::new()
+        .add_file("depth", "d.png", Some("image/png"), png(ImageLuma16(depth)))
+        .add_file("color", "c.png", Some("image/png"), png(ImageRgb16(color)))
+        .add_file("hdr", "h.png", Some("image/png"), png(ImageRgba16(hdr)))
+        .add_file("hdr8", "h.png", Some("image/png"), png(ImageRgba8(preview)))
+        .build();
+    let req = TestRequest::default().to_http_request();
+    let parsed = Depth::from_multipart(&req, mp).await.unwrap();
+
+    assert_eq!(parsed.depth.dimensions(), (3, 2));
+    assert!(parsed.depth.pixels().all(|p| p.0 == [0x1234]));
+    assert_eq!(parsed.color.get_pixel(0, 0).0, [1, 0x8000, 0xfffe]);
+
+    // Scaled to 0.0..=1.0 from 16 bits, or from 8 bits for 8-bit images
+    let scaled = |v: u16| f32::from(v) / 65535.0;
+    let expected = [scaled(0), scaled(0x8000), 1.0, scaled(0x1234)];
+    assert!(parsed.hdr.pixels().all(|p| p.0 == expected));
+    let expected = [1.0, 128.0 / 255.0, 0.0, 1.0];
+    let hdr8 = parsed.hdr8.get_pixel(0, 0).0;
+    assert!(hdr8
+        .iter()
+        .zip(&expected)
+        .all(|(v, e)| (v - e).abs() < 1e-6));
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use actix_web::test::{call_service, init_service, read_body};
+use actix_web::{post, App, HttpResponse};
+use awmpde::images::{ImageConfig, RgbImage};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FieldConfig, FromActixMultipart, MultipartForm};
+use common::png;
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    photos: Vec<RgbImage>,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    let sizes = upload
+        .photos
+        .iter()
+        .map(|img| format!("{:?}", img.dimensions()));
+    HttpResponse::Ok().body(sizes.collect::<Vec<_>>().join(" "))
+}
+
+#[actix_web::test]
+async fn app_data() {
+    let body = MultipartBuilder::new()
+        .add_file("photos", "a.png", Some("image/png"), png(16, 16))
+        .add_file("photos", "b.png", Some("image/png"), png(4, 4))
+        .add_file("photos", "c.png", Some("image/png"), png(2, 8))
+        .build_payload_bytes();
+    let request = || common::post("/upload", body.clone()).to_request();
+
+    let app = init_service(App::new().service(upload)).await;
+    let resp = call_service(&app, request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(read_body(resp).await, "(16, 16) (4, 4) (2, 8)");
+
+    // A single permit still decodes every image, one after another
+    let config = FieldConfig {
+        image: ImageConfig::default().max_concurrent(1),
+    };
+    let app = init_service(App::new().app_data(config).service(upload)).await;
+    let resp = call_service(&app, request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(read_body(resp).await, "(16, 16) (4, 4) (2, 8)");
+
+    let config = FieldConfig {
+        image: ImageConfig::default().inline().max_width(8),
+    };
+    let app = init_service(App::new().app_data(config).service(upload)).await;
+    let resp = call_service(&app, request()).await;
+    assert_eq!(resp.status().as_u16(), 413);
+}
+
+#[test]
+#[should_panic(expected = "can't be zero")]
+fn no_permits() {
+    ImageConfig::default().max_concurrent(0);
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::images::{ImageConfig, ImageLimits, RgbImage};
+use awmpde::{FieldConfig, FromActixMultipart, MultipartForm};
+use common::{crc32, encode, files};
+use image::ImageOutputFormat;
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    #[awmpde(max_width = 4096, max_height = 4096, max_pixels = 8_000_000)]
+    avatar: RgbImage,
+    #[awmpde(max_alloc = 1048576, image_formats = "png, jpeg, webp")]
+    thumbs: Vec<awmpde::File<RgbImage>>,
+    banner: Option<RgbImage>,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{:?} {} {:?}",
+        upload.avatar.dimensions(),
+        upload.thumbs.len(),
+        upload.banner.map(|banner| banner.dimensions())
+    ))
+}
+
+fn config() -> FieldConfig {
+    FieldConfig {
+        image: ImageConfig::default().limits(ImageLimits {
+            max_pixels: Some(40_000_000),
+            ..ImageLimits::default()
+        }),
+    }
+}
+
+/// 1x1 PNG claiming to be `width`x`height` in its header.
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut buf = encode(1, 1, ImageOutputFormat::Png);
+    buf[16..20].copy_from_slice(&width.to_be_bytes());
+    buf[20..24].copy_from_slice(&height.to_be_bytes());
+    let crc = crc32(&buf[12..29]);
+    buf[29..33].copy_from_slice(&crc.to_be_bytes());
+    buf
+}
+
+/// 1x1 JPEG claiming to be `width`x`height` in its header.
+fn jpeg(width: u16, height: u16) -> Vec<u8> {
+    let mut buf = encode(1, 1, ImageOutputFormat::Jpeg(80));
+    let sof = buf.windows(2).position(|m| m == [0xff, 0xc0]).unwrap();
+    buf[sof + 5..sof + 7].copy_from_slice(&height.to_be_bytes());
+    buf[sof + 7..sof + 9].copy_from_slice(&width.to_be_bytes());
+    buf
+}
+
+fn request(fields: Vec<(&'static str, &'static str, Vec<u8>)>) -> TestRequest {
+    common::post("/upload", files(fields).build_payload_bytes())
+}
+
+#[actix_web::test]
+async fn header_only() {
+    let app = init_service(App::new().service(upload)).await;
+
+    let req = request(vec![("avatar", "image/png", png(1, 1))]);
+    let resp = call_service(&app, req.to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(read_body(resp).await, "(1, 1) 0 None");
+
+    // Per field limits
+    let requests = vec![
+        vec![("avatar", "image/png", png(60000, 60000))],
+        vec![("avatar", "image/jpeg", jpeg(60000, 60000))],
+        vec![("avatar", "image/png", png(5000, 1))],
+        vec![
+            ("avatar", "image/png", png(1, 1)),
+            ("thumbs", "image/jpeg", jpeg(1000, 1000)),
+        ],
+    ];
+    for (i, fields) in requests.into_iter().enumerate() {
+        let resp = call_service(&app, request(fields).to_request()).await;
+        assert_eq!(resp.status().as_u16(), 413, "request {}", i);
+    }
+
+    // Only the header is valid, so a field within the limits fails to decode instead
+    let req = request(vec![
+        ("avatar", "image/png", png(1, 1)),
+        ("banner", "image/png", png(8000, 8000)),
+    ]);
+    let resp = call_service(&app, req.to_request()).await;
+    assert_ne!(resp.status().as_u16(), 413);
+    assert!(resp.status().is_client_error());
+}
+
+#[actix_web::test]
+async fn global() {
+    let app = init_service(App::new().app_data(config()).service(upload)).await;
+
+    let banners = [
+        ("image/png", png(60000, 60000)),
+        ("image/jpeg", jpeg(60000, 60000)),
+        ("image/png", png(8000, 8000)),
+        ("image/jpeg", jpeg(8000, 8000)),
+    ];
+    for (ct, banner) in banners.iter() {
+        let req = request(vec![
+            ("avatar", "image/png", png(1, 1)),
+            ("banner", ct, banner.clone()),
+        ]);
+        let resp = call_service(&app, req.to_request()).await;
+        assert_eq!(resp.status().as_u16(), 413, "{}", ct);
+    }
+}
@@ -1,7 +1,9 @@
-use awmpde::FromActixMultipart;
+use actix_web::test::TestRequest;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart};
 use serde::Deserialize;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, PartialEq)]
 enum State {
     Ready,
     Set,
@@ -11,5 +13,15 @@ enum State {
 #[derive(FromActixMultipart, Debug)]
 struct Help {
     #[serde_json]
-    _state: State,
+    state: State,
+}
+
+#[actix_web::test]
+async fn serde_json() {
+    let mp = MultipartBuilder::new()
+        .add_field("state", None, r#""Go""#.bytes())
+        .build();
+    let req = TestRequest::default().to_http_request();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+    assert_eq!(help.state, State::Go);
 }
@@ -0,0 +1,86 @@
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::futures::StreamExt;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, MultipartForm, MultipartStream};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+pub struct AnimalDesc {
+    kind: String,
+}
+
+#[derive(FromActixMultipart)]
+#[awmpde(stream)]
+pub struct IsAnimalRequest {
+    imgs: Vec<awmpde::File<Vec<u8>>>,
+    #[serde_json]
+    animal_desc: AnimalDesc,
+    #[awmpde(duplicate = "last")]
+    note: Option<String>,
+}
+
+#[post("/is_animal")]
+async fn is_animal(mut parts: MultipartStream<IsAnimalRequest>) -> HttpResponse {
+    let mut out = Vec::new();
+    while let Some(part) = parts.next().await {
+        match part {
+            Ok(IsAnimalRequestPart::Imgs(img)) => out.push(img.inner.len().to_string()),
+            Ok(IsAnimalRequestPart::AnimalDesc(desc)) => out.push(desc.kind),
+            Ok(IsAnimalRequestPart::Note(note)) => out.push(note),
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        }
+    }
+    HttpResponse::Ok().body(out.join(" "))
+}
+
+#[post("/is_animal/form")]
+async fn is_animal_form(MultipartForm(req): MultipartForm<IsAnimalRequest>) -> HttpResponse {
+    let lens = req.imgs.iter().map(|img| img.inner.len().to_string());
+    let mut out = lens.collect::<Vec<_>>();
+    out.push(req.animal_desc.kind);
+    out.extend(req.note);
+    HttpResponse::Ok().body(out.join(" "))
+}
+
+#[actix_web::test]
+async fn parts() {
+    let app = init_service(App::new().service(is_animal).service(is_animal_form)).await;
+    let body = MultipartBuilder::new()
+        .add_file("imgs", "a.png", Some("image/png"), vec![0; 3])
+        .add_field("animal_desc", None, r#"{"kind": "cat"}"#.bytes())
+        .add_file("imgs", "b.png", Some("image/png"), vec![0; 5])
+        .add_field("note", None, "old".bytes())
+        .add_field("note", None, "new".bytes())
+        .build_payload_bytes();
+
+    // The stream yields every part as sent, ignoring `duplicate`
+    let requests = [
+        ("/is_animal", 200, "3 cat 5 old new"),
+        ("/is_animal/form", 200, "3 5 cat new"),
+    ];
+    for (uri, status, expected) in requests.iter() {
+        let req = TestRequest::post()
+            .uri(uri)
+            .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+            .set_payload(body.clone())
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert_eq!(resp.status().as_u16(), *status, "{}", uri);
+        assert_eq!(read_body(resp).await, expected.as_bytes(), "{}", uri);
+    }
+
+    let req = TestRequest::post()
+        .uri("/is_animal")
+        .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+        .set_payload(
+            MultipartBuilder::new()
+                .add_field("note", None, "hi".bytes())
+                .add_field("owner", None, "me".bytes())
+                .build_payload_bytes(),
+        )
+        .to_request();
+    let resp = call_service(&app, req).await;
+    assert_eq!(resp.status().as_u16(), 400);
+}
@@ -0,0 +1,127 @@
+use actix_web::test::TestRequest;
+use actix_web::{post, HttpResponse};
+use awmpde::images::{ImageInfo, Probed};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart, MultipartForm};
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    preview: ImageInfo,
+    original: Probed,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    let Probed { info, inner } = upload.original;
+    HttpResponse::Ok().body(format!(
+        "{:?} {}x{} {} {} bytes",
+        info.format,
+        info.width,
+        info.height,
+        upload.preview.animated,
+        inner.len()
+    ))
+}
+
+const PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x64, 0x60, 0xf8, 0x5f,
+    0x0f, 0x00, 0x02, 0x87, 0x01, 0x80, 0xeb, 0x47, 0xba, 0x92, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+    0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+#[test]
+fn probe_png() {
+    use awmpde::images::{ColorType, ImageFormat};
+
+    let info = ImageInfo::probe(PNG, &mime::IMAGE_PNG).unwrap();
+    assert_eq!(
+        info,
+        ImageInfo {
+            format: ImageFormat::Png,
+            width: 1,
+            height: 1,
+            color_type: Some(ColorType::Rgba8),
+            animated: false,
+        }
+    );
+
+    // Format is guessed without content type
+    let info = ImageInfo::probe(PNG, &mime::APPLICATION_OCTET_STREAM).unwrap();
+    assert_eq!(info.format, ImageFormat::Png);
+
+    assert!(ImageInfo::probe(&PNG[..20], &mime::IMAGE_PNG).is_err());
+    assert!(matches!(
+        ImageInfo::probe(PNG, &mime::IMAGE_JPEG),
+        Err(awmpde::Error::ImageFormatMismatch(_, ImageFormat::Png))
+    ));
+}
+
+fn gif(frames: usize, repeat: bool) -> Vec<u8> {
+    use image::codecs::gif::{GifEncoder, Repeat};
+    use image::{Frame, Rgba, RgbaImage};
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut buf);
+        if repeat {
+            encoder.set_repeat(Repeat::Infinite).unwrap();
+        }
+        for i in 0..frames {
+            let img = RgbaImage::from_pixel(3, 2, Rgba([i as u8 * 100, 0, 0, 255]));
+            encoder.encode_frame(Frame::new(img)).unwrap();
+        }
+    }
+    buf
+}
+
+/// Multipart of `body` delivered in chunks of `size` bytes.
+fn chunked(body: &[u8], size: usize) -> awmpde::actix_multipart::Multipart {
+    use actix_web::error::PayloadError;
+    use actix_web::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use actix_web::web::Bytes;
+
+    let mut headers = HeaderMap::new();
+    let ct = HeaderValue::from_static(MultipartBuilder::CONTENT_TYPE);
+    headers.insert(CONTENT_TYPE, ct);
+    let chunks = body
+        .chunks(size)
+        .map(|chunk| Ok::<_, PayloadError>(Bytes::copy_from_slice(chunk)))
+        .collect::<Vec<_>>();
+    awmpde::actix_multipart::Multipart::new(&headers, awmpde::futures::stream::iter(chunks))
+}
+
+#[actix_web::test]
+async fn split_gif() {
+    let req = TestRequest::default().to_http_request();
+    let images = [
+        (gif(2, true), true),
+        (gif(3, false), true),
+        (gif(1, false), false),
+    ];
+
+    for (img, animated) in images.iter() {
+        let body = MultipartBuilder::new()
+            .add_field("preview", Some("image/gif"), img.iter().copied())
+            .add_field("original", Some("image/gif"), img.iter().copied())
+            .build_payload_bytes();
+
+        for size in (1..24).chain(Some(body.len())) {
+            let parsed = Upload::from_multipart(&req, chunked(&body, size))
+                .await
+                .unwrap();
+            assert_eq!(parsed.preview.animated, *animated, "chunks of {}", size);
+            assert_eq!(
+                parsed.original.info.animated, *animated,
+                "chunks of {}",
+                size
+            );
+            assert_eq!(
+                (parsed.original.info.width, parsed.original.info.height),
+                (3, 2)
+            );
+            assert_eq!(&parsed.original.inner, img);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "protobuf")]
+
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, MultipartForm};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+struct Pet {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(uint32, tag = "2")]
+    age: u32,
+}
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    #[awmpde(format = "protobuf")]
+    pet: Pet,
+    #[awmpde(format = "protobuf", limit = 8)]
+    small: Option<Pet>,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    let small = upload.small.map(|pet| pet.name);
+    HttpResponse::Ok().body(format!(
+        "{} {} {:?}",
+        upload.pet.name, upload.pet.age, small
+    ))
+}
+
+fn pet(name: &str, age: u32) -> Vec<u8> {
+    let name = name.to_string();
+    Pet { name, age }.encode_to_vec()
+}
+
+#[actix_web::test]
+async fn protobuf() {
+    let app = init_service(App::new().service(upload)).await;
+
+    let requests = vec![
+        (
+            vec![("pet", "application/protobuf", pet("Rex", 3))],
+            200,
+            "Rex 3 None",
+        ),
+        (
+            vec![
+                ("pet", "application/x-protobuf", pet("Tom", 1)),
+                ("small", "application/protobuf", pet("Kit", 2)),
+            ],
+            200,
+            r#"Tom 1 Some("Kit")"#,
+        ),
+        (vec![("pet", "application/json", pet("Rex", 3))], 415, ""),
+        (
+            vec![("pet", "application/protobuf", vec![0x0a, 0x05])],
+            400,
+            "",
+        ),
+        // Over the field's limit of 8 bytes
+        (
+            vec![
+                ("pet", "application/protobuf", pet("Rex", 3)),
+                ("small", "application/protobuf", pet("Kitten", 2)),
+            ],
+            413,
+            "",
+        ),
+    ];
+
+    for (fields, status, out) in requests {
+        let body = fields
+            .into_iter()
+            .fold(MultipartBuilder::new(), |mp, (name, ct, content)| {
+                mp.add_field(name, Some(ct), content)
+            })
+            .build_payload_bytes();
+        let req = TestRequest::post()
+            .uri("/upload")
+            .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+            .set_payload(body)
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(resp.status(), status, "{}", out);
+        if status == 200 {
+            assert_eq!(read_body(resp).await, out);
+        }
+    }
+}
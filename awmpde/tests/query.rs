@@ -0,0 +1,109 @@
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, MultipartForm};
+
+#[derive(FromActixMultipart)]
+#[awmpde(query, query_precedence = "query")]
+struct Upload {
+    album: u32,
+    overwrite: Option<bool>,
+    tags: Vec<String>,
+}
+
+#[derive(FromActixMultipart)]
+#[awmpde(query_precedence = "error")]
+struct Rename {
+    #[awmpde(from_query)]
+    id: u32,
+    name: String,
+}
+
+#[derive(FromActixMultipart)]
+struct Search {
+    #[awmpde(from_query)]
+    page: Option<u32>,
+    text: String,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{} {:?} {:?}",
+        upload.album, upload.overwrite, upload.tags
+    ))
+}
+
+#[post("/rename")]
+async fn rename(MultipartForm(rename): MultipartForm<Rename>) -> HttpResponse {
+    HttpResponse::Ok().body(format!("{} {}", rename.id, rename.name))
+}
+
+#[post("/search")]
+async fn search(MultipartForm(search): MultipartForm<Search>) -> HttpResponse {
+    HttpResponse::Ok().body(format!("{:?} {}", search.page, search.text))
+}
+
+/// Name and value of a body part.
+type Field = (&'static str, &'static str);
+
+#[actix_web::test]
+async fn precedence() {
+    let app = init_service(App::new().service(upload).service(rename).service(search)).await;
+
+    let requests: &[(&str, &[Field], u16, &str)] = &[
+        // Body wins by default
+        (
+            "/search?page=2",
+            &[("text", "cats"), ("page", "1")],
+            200,
+            "Some(1) cats",
+        ),
+        ("/search?page=2", &[("text", "cats")], 200, "Some(2) cats"),
+        ("/search?text=dogs", &[("text", "cats")], 200, "None cats"),
+        // Query wins, repeated parameters fill a `Vec`, unknown ones are ignored
+        (
+            "/upload?album=2&tags=a&tags=b&owner=me",
+            &[("album", "1"), ("tags", "x"), ("overwrite", "true")],
+            200,
+            r#"2 Some(true) ["a", "b"]"#,
+        ),
+        (
+            "/upload?al%62um=7&over%77rite=false",
+            &[],
+            200,
+            "7 Some(false) []",
+        ),
+        (
+            "/upload?tags=a%20b",
+            &[("album", "1")],
+            200,
+            r#"1 None ["a b"]"#,
+        ),
+        // With `error` a field can come from either, but not both
+        ("/rename?id=1&owner=me", &[("name", "cat")], 200, "1 cat"),
+        ("/rename", &[("id", "2"), ("name", "cat")], 200, "2 cat"),
+        ("/rename?id=1", &[("id", "2"), ("name", "cat")], 400, ""),
+    ];
+
+    for &(uri, fields, status, out) in requests {
+        let body = fields
+            .iter()
+            .fold(MultipartBuilder::new(), |mp, (name, value)| {
+                mp.add_field(name, None, value.bytes())
+            })
+            .build_payload_bytes();
+        let req = TestRequest::post()
+            .uri(uri)
+            .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+            .set_payload(body)
+            .to_request();
+        let resp = call_service(&app, req).await;
+
+        assert_eq!(resp.status(), status, "{}", uri);
+        if status == 200 {
+            assert_eq!(read_body(resp).await, out, "{}", uri);
+        }
+    }
+}
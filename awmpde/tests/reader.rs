@@ -0,0 +1,79 @@
+use actix_web::http::header::CONTENT_TYPE;
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::actix_multipart::Field;
+use awmpde::futures::future::{FutureExt, LocalBoxFuture};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FieldReader, FieldStream, FromActixMultipart, FromField, MultipartForm};
+use tokio::io::AsyncReadExt;
+
+/// Number of lines in a part, counted without buffering it.
+struct Lines(usize);
+
+impl FromField for Lines {
+    type Error = awmpde::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: Field) -> Self::Future {
+        async move {
+            let mut reader = FieldReader::new(field).with_limit(64);
+            let mut buf = [0; 4096];
+            let mut lines = 0;
+            loop {
+                let n = reader.read(&mut buf).await?;
+                if n == 0 {
+                    break Ok(Lines(lines));
+                }
+                lines += buf[..n].iter().filter(|b| **b == b'\n').count();
+            }
+        }
+        .boxed_local()
+    }
+}
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    notes: Lines,
+    file: FieldStream,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    let mut reader = upload.file.into_reader();
+    let copied = tokio::io::copy(&mut reader, &mut tokio::io::sink())
+        .await
+        .unwrap();
+    HttpResponse::Ok().body(format!("{} {}", upload.notes.0, copied))
+}
+
+#[actix_web::test]
+async fn limit() {
+    let app = init_service(App::new().service(upload)).await;
+    let request = |body| {
+        TestRequest::post()
+            .uri("/upload")
+            .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+            .set_payload(body)
+            .to_request()
+    };
+    let body = |notes: &str| {
+        MultipartBuilder::new()
+            .add_field("notes", None, notes.bytes())
+            .add_file("file", "a.bin", None, vec![0; 100])
+            .build_payload_bytes()
+    };
+
+    let resp = call_service(&app, request(body("a\nb\nc\n"))).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(read_body(resp).await, "3 100");
+
+    let resp = call_service(&app, request(body(&"a\n".repeat(40)))).await;
+    assert_eq!(resp.status().as_u16(), 413);
+
+    // Body cut off inside the field
+    let mut truncated = body("a\nb\nc\n").to_vec();
+    let notes = truncated.windows(3).position(|w| w == b"a\nb").unwrap();
+    truncated.truncate(notes + 3);
+    let resp = call_service(&app, request(truncated.into())).await;
+    assert_eq!(resp.status().as_u16(), 400);
+}
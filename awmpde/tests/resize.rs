@@ -0,0 +1,74 @@
+mod common;
+
+use actix_web::test::{call_service, init_service, read_body};
+use actix_web::{post, App, HttpResponse};
+use awmpde::images::{Resized, RgbImage, RgbaImage, Thumbnail};
+use awmpde::{FromActixMultipart, MultipartForm};
+use common::{encode, files, png};
+use image::ImageOutputFormat;
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    #[awmpde(resize(max_w = 1024, max_h = 1024, filter = "lanczos3", mode = "fit"))]
+    photo: Resized<RgbImage>,
+    #[awmpde(resize(max_w = 128, max_h = 128, mode = "fill"))]
+    avatar: Thumbnail<RgbaImage>,
+    preview: Thumbnail<RgbImage>,
+    #[awmpde(resize(max_w = 64, max_h = 32, mode = "exact"))]
+    banner: Option<Resized<RgbImage>>,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(upload): MultipartForm<Upload>) -> HttpResponse {
+    HttpResponse::Ok().body(format!(
+        "{:?} {:?} {:?} {:?}",
+        upload.photo.dimensions(),
+        upload.avatar.dimensions(),
+        upload.preview.dimensions(),
+        upload.banner.map(|banner| banner.dimensions())
+    ))
+}
+
+#[actix_web::test]
+async fn sizes() {
+    let app = init_service(App::new().service(upload)).await;
+    let jpeg = encode(1024, 512, ImageOutputFormat::Jpeg(80));
+
+    let requests = vec![
+        (
+            vec![
+                ("photo", "image/png", png(1200, 600)),
+                ("avatar", "image/png", png(300, 200)),
+                ("preview", "image/png", png(1000, 500)),
+                ("banner", "image/png", png(100, 100)),
+            ],
+            "(1024, 512) (128, 128) (256, 128) Some((64, 32))",
+        ),
+        // Fit never upscales, fill and exact do
+        (
+            vec![
+                ("photo", "image/png", png(500, 300)),
+                ("avatar", "image/png", png(64, 32)),
+                ("preview", "image/png", png(100, 600)),
+                ("banner", "image/png", png(8, 8)),
+            ],
+            "(500, 300) (128, 128) (43, 256) Some((64, 32))",
+        ),
+        // JPEGs decoded at a smaller DCT scale still give the same size
+        (
+            vec![
+                ("photo", "image/jpeg", jpeg.clone()),
+                ("avatar", "image/jpeg", jpeg.clone()),
+                ("preview", "image/jpeg", jpeg),
+            ],
+            "(1024, 512) (128, 128) (256, 128) None",
+        ),
+    ];
+
+    for (fields, expected) in requests {
+        let body = files(fields).build_payload_bytes();
+        let resp = call_service(&app, common::post("/upload", body).to_request()).await;
+        assert_eq!(resp.status().as_u16(), 200, "{}", expected);
+        assert_eq!(read_body(resp).await, expected.as_bytes());
+    }
+}
@@ -0,0 +1,28 @@
+use awmpde::de::ScalarDeserializer;
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum State {
+    Ready,
+    Go,
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(u32::deserialize(ScalarDeserializer("42")).unwrap(), 42);
+    assert!(bool::deserialize(ScalarDeserializer("true")).unwrap());
+    assert_eq!(
+        String::deserialize(ScalarDeserializer("cat")).unwrap(),
+        "cat"
+    );
+    assert_eq!(
+        State::deserialize(ScalarDeserializer("go")).unwrap(),
+        State::Go
+    );
+    assert_eq!(
+        Option::<u8>::deserialize(ScalarDeserializer("")).unwrap(),
+        None
+    );
+    assert!(u8::deserialize(ScalarDeserializer("dog")).is_err());
+}
@@ -0,0 +1,166 @@
+use awmpde::de::{from_parts, Part};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Upload {
+    #[serde(rename = "album")]
+    album_id: u32,
+    #[serde(default)]
+    overwrite: bool,
+    tags: Vec<String>,
+    note: Option<String>,
+    img: awmpde::File<Vec<u8>>,
+}
+
+fn file(name: &str, data: &[u8]) -> Part {
+    Part {
+        filename: Some(name.to_string()),
+        mime: mime::IMAGE_PNG,
+        data: data.to_vec(),
+    }
+}
+
+#[test]
+fn deserialize_parts() {
+    let parts = vec![
+        ("album".to_string(), Part::text("42".to_string())),
+        ("tags".to_string(), Part::text("cat".to_string())),
+        ("img".to_string(), file("cat.png", b"\x89PNG")),
+        ("tags".to_string(), Part::text("dog".to_string())),
+        ("note".to_string(), Part::text(String::new())),
+    ];
+    let up: Upload = from_parts(parts).unwrap();
+
+    assert_eq!(up.album_id, 42);
+    assert!(!up.overwrite);
+    assert_eq!(up.tags, ["cat", "dog"]);
+    assert_eq!(up.note, None);
+    assert_eq!(up.img.name.to_str(), Some("cat.png"));
+    assert_eq!(up.img.mime, mime::IMAGE_PNG);
+    assert_eq!(up.img.inner, b"\x89PNG");
+}
+
+#[test]
+fn unknown_field() {
+    let parts = vec![
+        ("album".to_string(), Part::text("1".to_string())),
+        ("other".to_string(), Part::text("1".to_string())),
+    ];
+    assert!(from_parts::<Upload, _>(parts).is_err());
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Page {
+    page: u32,
+    sort: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Search {
+    query: String,
+    #[serde(flatten)]
+    page: Page,
+}
+
+#[test]
+fn flatten() {
+    let parts = vec![
+        ("query".to_string(), Part::text("cats".to_string())),
+        ("page".to_string(), Part::text("3".to_string())),
+        ("sort".to_string(), Part::text("date".to_string())),
+    ];
+    let search: Search = from_parts(parts).unwrap();
+
+    assert_eq!(search.query, "cats");
+    assert_eq!(
+        search.page,
+        Page {
+            page: 3,
+            sort: "date".to_string()
+        }
+    );
+}
+
+#[actix_web::test]
+async fn requests() {
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::{web, App, HttpResponse};
+    use awmpde::test::MultipartBuilder;
+    use awmpde::{FormOrMultipart, MultipartForm, Serde};
+
+    async fn search(form: FormOrMultipart<Serde<Search>>) -> HttpResponse {
+        HttpResponse::Ok().body(format!("{} {}", form.query, form.page.page))
+    }
+
+    async fn small(_form: MultipartForm<Serde<Search, 3>>) -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    async fn total(_form: MultipartForm<Serde<Search, 1024, 8>>) -> HttpResponse {
+        HttpResponse::Ok().finish()
+    }
+
+    let app = init_service(
+        App::new()
+            .route("/search", web::post().to(search))
+            .route("/small", web::post().to(small))
+            .route("/total", web::post().to(total)),
+    )
+    .await;
+    let body = MultipartBuilder::new()
+        .add_field("query", None, "cats".bytes())
+        .add_field("page", None, "2".bytes())
+        .add_field("sort", None, "name".bytes())
+        .build_payload_bytes();
+
+    let req = TestRequest::post()
+        .uri("/search")
+        .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+        .set_payload(body.clone())
+        .to_request();
+    assert_eq!(read_body(call_service(&app, req).await).await, "cats 2");
+
+    // Url-encoded forms go through the same deserializer
+    let req = TestRequest::post()
+        .uri("/search")
+        .insert_header((CONTENT_TYPE, "application/x-www-form-urlencoded"))
+        .set_payload("query=dogs&page=5&sort=name")
+        .to_request();
+    assert_eq!(read_body(call_service(&app, req).await).await, "dogs 5");
+
+    let req = TestRequest::post()
+        .uri("/small")
+        .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+        .set_payload(body.clone())
+        .to_request();
+    assert_eq!(call_service(&app, req).await.status(), 413);
+
+    let multipart = |uri, body| {
+        TestRequest::post()
+            .uri(uri)
+            .insert_header((CONTENT_TYPE, MultipartBuilder::CONTENT_TYPE))
+            .set_payload(body)
+            .to_request()
+    };
+
+    // Each part is within `LIMIT`, all of them are over `TOTAL`
+    let req = multipart("/total", body.clone());
+    assert_eq!(call_service(&app, req).await.status(), 413);
+
+    let parts = (0..=awmpde::de::MAX_PARTS)
+        .fold(MultipartBuilder::new(), |mp, _| {
+            mp.add_field("sort", None, None)
+        })
+        .build_payload_bytes();
+    let req = multipart("/search", parts);
+    assert_eq!(call_service(&app, req).await.status(), 413);
+
+    // Part without a name
+    let nameless = String::from_utf8(body.to_vec())
+        .unwrap()
+        .replace("name=\"sort\"", "id=\"sort\"");
+    let req = multipart("/search", nameless.into());
+    assert_eq!(call_service(&app, req).await.status(), 400);
+}
@@ -0,0 +1,83 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::task::Poll;
+
+use actix_web::error::PayloadError;
+use actix_web::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use actix_web::test::TestRequest;
+use actix_web::web::Bytes;
+use actix_web::{post, HttpResponse};
+use awmpde::futures::{stream, StreamExt};
+use awmpde::test::MultipartBuilder;
+use awmpde::{FieldStream, FromActixMultipart, FromMultipart, MultipartForm};
+
+#[derive(FromActixMultipart)]
+struct Upload {
+    title: String,
+    tags: Vec<String>,
+    file: FieldStream,
+}
+
+#[post("/upload")]
+async fn upload(MultipartForm(mut upload): MultipartForm<Upload>) -> HttpResponse {
+    let mut len = 0;
+    while let Some(chunk) = upload.file.next().await {
+        len += chunk.unwrap().len();
+    }
+    HttpResponse::Ok().body(format!("{} {:?} {}", upload.title, upload.tags, len))
+}
+
+#[actix_web::test]
+async fn unread() {
+    let file = (0..4096).map(|i| i as u8).collect::<Vec<_>>();
+    let body = MultipartBuilder::new()
+        .add_field("title", None, "cats".bytes())
+        .add_field("tags", None, "a".bytes())
+        .add_field("tags", None, "b".bytes())
+        .add_file("file", "cats.bin", None, file.clone())
+        .add_field("after", None, vec![b'x'; 1 << 16])
+        .build_payload_bytes();
+
+    // Payload in small chunks, one per poll like a slow upload, counting how much was read
+    let read = Rc::new(Cell::new(0));
+    let mut chunks = body
+        .chunks(64)
+        .map(Bytes::copy_from_slice)
+        .collect::<Vec<_>>();
+    chunks.reverse();
+    let mut ready = false;
+    let payload = stream::poll_fn({
+        let read = read.clone();
+        move |cx| {
+            ready = !ready;
+            if !ready {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(chunks.pop().map(|chunk| {
+                read.set(read.get() + chunk.len());
+                Ok::<_, PayloadError>(chunk)
+            }))
+        }
+    });
+    let mut headers = HeaderMap::new();
+    let ct = HeaderValue::from_static(MultipartBuilder::CONTENT_TYPE);
+    headers.insert(CONTENT_TYPE, ct);
+    let mp = awmpde::actix_multipart::Multipart::new(&headers, payload);
+
+    let req = TestRequest::default().to_http_request();
+    let mut parsed = Upload::from_multipart(&req, mp).await.unwrap();
+    assert_eq!(parsed.title, "cats");
+    assert_eq!(parsed.tags, ["a", "b"]);
+    assert_eq!(parsed.file.filename().unwrap().to_str(), Some("cats.bin"));
+    // At most the first chunk of the file was read
+    assert!(read.get() < file.len() / 2);
+
+    let mut streamed = Vec::new();
+    while let Some(chunk) = parsed.file.next().await {
+        streamed.extend_from_slice(&chunk.unwrap());
+    }
+    assert_eq!(streamed, file);
+    // The part after it is left in the payload
+    assert!(read.get() < body.len() - (1 << 15));
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "ndarray")]
+
+mod common;
+
+use actix_web::test::{call_service, init_service, read_body, TestRequest};
+use actix_web::{post, App, HttpResponse};
+use awmpde::images::{Array3, Tensor};
+use awmpde::{FromActixMultipart, FromMultipart, MultipartForm};
+use common::files;
+
+#[derive(FromActixMultipart)]
+struct Inference {
+    #[awmpde(tensor(
+        width = 224,
+        height = 224,
+        layout = "chw",
+        mean = "0.485, 0.456, 0.406",
+        std = "0.229, 0.224, 0.225"
+    ))]
+    batch: Vec<Tensor>,
+    #[awmpde(tensor(width = 640, height = 640, letterbox))]
+    scene: Tensor,
+}
+
+#[post("/infer")]
+async fn infer(MultipartForm(req): MultipartForm<Inference>) -> HttpResponse {
+    let batch = Tensor::stack(&req.batch).unwrap();
+    HttpResponse::Ok().body(format!("{:?} {:?}", batch.shape(), req.scene.shape()))
+}
+
+#[test]
+fn stack() {
+    let tensors = vec![Tensor(Array3::zeros((3, 2, 2))); 4];
+    assert_eq!(Tensor::stack(&tensors).unwrap().shape(), &[4, 3, 2, 2]);
+}
+
+#[derive(FromActixMultipart)]
+struct Shapes {
+    #[awmpde(tensor(layout = "hwc"))]
+    hwc: Tensor,
+    #[awmpde(tensor(layout = "chw"))]
+    chw: Tensor,
+    #[awmpde(tensor(mean = "0.5, 0.5, 0.5", std = "0.5, 0.25, 1.0"))]
+    normalized: Tensor,
+    #[awmpde(tensor(width = 8, height = 8, letterbox))]
+    letterbox: Tensor,
+}
+
+fn png(img: image::RgbImage) -> Vec<u8> {
+    common::png_of(image::DynamicImage::ImageRgb8(img))
+}
+
+#[actix_web::test]
+async fn shapes() {
+    use image::{Rgb, RgbImage};
+
+    // Red pixel left of a blue one
+    let mut pair = RgbImage::new(2, 1);
+    pair.put_pixel(0, 0, Rgb([255, 0, 0]));
+    pair.put_pixel(1, 0, Rgb([0, 0, 255]));
+    let orange = RgbImage::from_pixel(2, 2, Rgb([255, 0, 128]));
+    let white = RgbImage::from_pixel(4, 2, Rgb([255, 255, 255]));
+
+    let mp = files(vec![
+        ("hwc", "image/png", png(pair.clone())),
+        ("chw", "image/png", png(pair)),
+        ("normalized", "image/png", png(orange)),
+        ("letterbox", "image/png", png(white)),
+    ]);
+    let req = TestRequest::default().to_http_request();
+    let shapes = Shapes::from_multipart(&req, mp.build()).await.unwrap();
+
+    assert_eq!(shapes.hwc.shape(), &[1, 2, 3]);
+    assert_eq!(shapes.hwc[[0, 0, 0]], 1.0);
+    assert_eq!(shapes.hwc[[0, 1, 2]], 1.0);
+    assert_eq!(shapes.hwc[[0, 1, 0]], 0.0);
+
+    assert_eq!(shapes.chw.shape(), &[3, 1, 2]);
+    assert_eq!(shapes.chw[[0, 0, 0]], 1.0);
+    assert_eq!(shapes.chw[[2, 0, 1]], 1.0);
+    assert_eq!(shapes.chw[[0, 0, 1]], 0.0);
+
+    // (value / 255 - mean) / std
+    let expected = [1.0, -2.0, 128.0 / 255.0 - 0.5];
+    for (c, expected) in expected.iter().enumerate() {
+        assert!((shapes.normalized[[1, 1, c]] - expected).abs() < 1e-6);
+    }
+
+    // Scaled to 8x4 and centered, two black rows above and below
+    assert_eq!(shapes.letterbox.shape(), &[8, 8, 3]);
+    for ((y, _, _), v) in shapes.letterbox.indexed_iter() {
+        if (2..6).contains(&y) {
+            assert!(*v > 0.99, "row {}", y);
+        } else {
+            assert_eq!(*v, 0.0, "row {}", y);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn batch() {
+    use image::RgbImage;
+
+    let app = init_service(App::new().service(infer)).await;
+    let body = files(vec![
+        ("batch", "image/png", png(RgbImage::new(300, 200))),
+        ("batch", "image/png", png(RgbImage::new(20, 40))),
+        ("batch", "image/png", png(RgbImage::new(224, 224))),
+        ("scene", "image/png", png(RgbImage::new(100, 50))),
+    ])
+    .build_payload_bytes();
+    let resp = call_service(&app, common::post("/infer", body).to_request()).await;
+    assert_eq!(resp.status().as_u16(), 200);
+    assert_eq!(read_body(resp).await, "[3, 3, 224, 224] [640, 640, 3]");
+}
@@ -1,6 +1,19 @@
-use awmpde::FromActixMultipart;
+use actix_web::test::TestRequest;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart};
 
 #[derive(FromActixMultipart)]
 struct Help {
-    _animals: Vec<String>,
+    animals: Vec<String>,
+}
+
+#[actix_web::test]
+async fn repeated() {
+    let mp = MultipartBuilder::new()
+        .add_field("animals", None, "cat".bytes())
+        .add_field("animals", None, "dog".bytes())
+        .build();
+    let req = TestRequest::default().to_http_request();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+    assert_eq!(help.animals, ["cat", "dog"]);
 }
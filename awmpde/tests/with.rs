@@ -0,0 +1,49 @@
+use actix_web::test::TestRequest;
+use awmpde::test::MultipartBuilder;
+use awmpde::{FromActixMultipart, FromMultipart};
+
+mod comma_separated {
+    use actix_web::error::ErrorBadRequest;
+
+    pub fn parse(bytes: Vec<u8>) -> Result<Vec<u32>, actix_web::Error> {
+        std::str::from_utf8(&bytes)
+            .map_err(ErrorBadRequest)?
+            .split(',')
+            .map(|s| s.trim().parse().map_err(ErrorBadRequest))
+            .collect()
+    }
+}
+
+mod upper {
+    pub async fn from_field(
+        field: awmpde::actix_multipart::Field,
+    ) -> Result<String, awmpde::Error> {
+        let s = <String as awmpde::FromField>::from_field(field).await?;
+        Ok(s.to_uppercase())
+    }
+}
+
+#[derive(FromActixMultipart)]
+struct Help {
+    #[awmpde(parse_with = "comma_separated::parse")]
+    ids: Vec<u32>,
+    #[awmpde(with = "upper")]
+    name: Option<String>,
+}
+
+#[actix_web::test]
+async fn custom_parsers() {
+    let req = TestRequest::default().to_http_request();
+    let mp = MultipartBuilder::new()
+        .add_field("ids", None, "1, 2,3".bytes())
+        .add_field("name", None, "rex".bytes())
+        .build();
+    let help = Help::from_multipart(&req, mp).await.unwrap();
+    assert_eq!(help.ids, [1, 2, 3]);
+    assert_eq!(help.name.as_deref(), Some("REX"));
+
+    let mp = MultipartBuilder::new()
+        .add_field("ids", None, "1,two".bytes())
+        .build();
+    assert!(Help::from_multipart(&req, mp).await.is_err());
+}
@@ -1,8 +1,11 @@
+use crate::opts::{ContainerOpts, Duplicate, FieldOpts, Precedence};
+
 use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
 use quote::quote;
 use syn::{
     parse_macro_input, Attribute, Data, DataStruct, DeriveInput, Field, Fields, FieldsNamed,
-    GenericArgument, PathArguments, Type, TypePath,
+    GenericArgument, Ident, PathArguments, Type, TypePath,
 };
 
 fn from_json_attr(f: &Field) -> Option<&Attribute> {
@@ -11,7 +14,14 @@ fn from_json_attr(f: &Field) -> Option<&Attribute> {
         .find(|attr| attr.path.segments.len() == 1 && attr.path.segments[0].ident == "serde_json")
 }
 
-fn get_struct_arg<'a, 'b>(s: &'a str, ty: &'b TypePath) -> Option<&'b GenericArgument> {
+fn is_field_stream(ty: &TypePath) -> bool {
+    ty.path
+        .segments
+        .last()
+        .map_or(false, |seg| seg.ident == "FieldStream")
+}
+
+fn get_struct_arg<'a>(s: &str, ty: &'a TypePath) -> Option<&'a GenericArgument> {
     let segs = &ty.path.segments;
     if segs.len() != 1 || segs[0].ident != s {
         return None;
@@ -25,6 +35,310 @@ fn get_struct_arg<'a, 'b>(s: &'a str, ty: &'b TypePath) -> Option<&'b GenericArg
     }
 }
 
+/// How many parts with the field's name are collected into it.
+enum Kind<'a> {
+    /// Exactly one part, type of the field itself.
+    Single(&'a Type),
+    /// At most one part, `Option<T>`.
+    Optional(&'a GenericArgument),
+    /// Any number of parts, `Vec<T>`.
+    Repeated(&'a GenericArgument),
+    /// Last part left unread, `FieldStream`.
+    Stream,
+}
+
+struct MpField<'a> {
+    name: &'a Ident,
+    kind: Kind<'a>,
+    opts: FieldOpts,
+}
+
+impl<'a> MpField<'a> {
+    fn new(f: &'a Field) -> syn::Result<Self> {
+        let mut opts = FieldOpts::from_attrs(&f.attrs)?;
+        if let Some(attr) = from_json_attr(f) {
+            if opts.format.is_some() {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "`#[serde_json]` conflicts with `#[awmpde(format)]`",
+                ));
+            }
+            opts.format = Some(Ident::new("Json", attr.path.segments[0].ident.span()));
+        }
+        // Custom parsers produce the whole `Vec` from one part
+        let whole = opts.with.is_some() || opts.parse_with.is_some();
+
+        let typ = match f.ty {
+            Type::Path(ref typ) => typ,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &f.ty,
+                    "field type must be a path, such as `String` or `Vec<T>`",
+                ))
+            }
+        };
+        let mut kind = Kind::Single(&f.ty);
+        if is_field_stream(typ) {
+            kind = Kind::Stream;
+        } else if let Some(vty) = get_struct_arg("Option", typ) {
+            kind = Kind::Optional(vty);
+        } else if let Some(vty) = get_struct_arg("Vec", typ) {
+            if !whole {
+                kind = Kind::Repeated(vty);
+            }
+        }
+
+        if matches!(kind, Kind::Stream) && (whole || opts.format.is_some()) {
+            return Err(syn::Error::new_spanned(
+                &f.ty,
+                "`FieldStream` can't be parsed with `with`, `parse_with` or `format`",
+            ));
+        }
+
+        Ok(Self {
+            name: f.ident.as_ref().unwrap(),
+            kind,
+            opts,
+        })
+    }
+
+    fn struct_field(&self) -> TokenStream2 {
+        let name = self.name;
+        match self.kind {
+            Kind::Single(ty) => {
+                quote! { #name: std::result::Result<awmpde::Deferred<#ty>, awmpde::Error> }
+            }
+            Kind::Optional(vty) => quote! { #name: std::option::Option<awmpde::Deferred<#vty>> },
+            Kind::Repeated(vty) => quote! { #name: std::vec::Vec<awmpde::Deferred<#vty>> },
+            Kind::Stream => quote! { #name: std::option::Option<awmpde::FieldStream> },
+        }
+    }
+
+    fn initial_value(&self) -> TokenStream2 {
+        let name = self.name;
+        match self.kind {
+            Kind::Single(_) => quote! {
+                #name: std::result::Result::Err(awmpde::Error::FieldError(stringify!(#name)))
+            },
+            Kind::Optional(_) | Kind::Stream => quote! { #name: std::option::Option::None },
+            Kind::Repeated(_) => quote! { #name: std::vec::Vec::new() },
+        }
+    }
+
+    /// Expression parsing `field` into a single value of the field.
+    fn parse(&self) -> TokenStream2 {
+        let ty = match self.kind {
+            Kind::Single(ty) => quote! { #ty },
+            Kind::Optional(vty) | Kind::Repeated(vty) => quote! { #vty },
+            Kind::Stream => unreachable!("`FieldStream` is never parsed"),
+        };
+
+        if let Some(format) = &self.opts.format {
+            let limit = self.opts.limit.iter();
+            quote! {
+                <awmpde::#format<#ty #(, #limit)*> as awmpde::FromField>::from_field(field)
+                    .await?
+                    .0
+            }
+        } else if let Some(with) = &self.opts.with {
+            quote! {
+                #with::from_field(field)
+                    .await
+                    .map_err(|e| awmpde::Error::ActixWebError(e.into()))?
+            }
+        } else if let Some(parse_with) = &self.opts.parse_with {
+            quote! {
+                #parse_with(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?)
+                    .map_err(|e| awmpde::Error::ActixWebError(e.into()))?
+            }
+        } else {
+            quote! {
+                <#ty as awmpde::FromField>::from_field(field).await?
+            }
+        }
+    }
+
+    /// Expression reading `field` into `awmpde::Deferred` value of the field.
+    fn deferred(&self) -> TokenStream2 {
+        let custom = self.opts.format.is_some()
+            || self.opts.with.is_some()
+            || self.opts.parse_with.is_some();
+        if custom {
+            let parse = self.parse();
+            return quote! { awmpde::futures::future::ok::<_, awmpde::Error>(#parse).boxed_local() };
+        }
+
+        let ty = match self.kind {
+            Kind::Single(ty) => quote! { #ty },
+            Kind::Optional(vty) | Kind::Repeated(vty) => quote! { #vty },
+            Kind::Stream => unreachable!("`FieldStream` is never parsed"),
+        };
+        if self.opts.image.is_empty() {
+            return quote! { awmpde::read_deferred::<#ty>(field, config).await? };
+        }
+
+        let image = &self.opts.image;
+        quote! {{
+            let mut config = config.clone();
+            config.image = config.image #(#image)*;
+            awmpde::read_deferred::<#ty>(field, &config).await?
+        }}
+    }
+
+    /// Match arm body storing a newly arrived part.
+    fn matched(&self) -> TokenStream2 {
+        let name = self.name;
+        match self.kind {
+            Kind::Single(_) => {
+                let parse = self.deferred();
+                let store = quote! {{ mpstruct.#name = Ok(#parse); }};
+                let present = quote! { mpstruct.#name.is_ok() };
+                store_once(name, present, self.opts.duplicate, store)
+            }
+            Kind::Optional(_) => {
+                let parse = self.deferred();
+                let store = quote! {{ mpstruct.#name = Some(#parse); }};
+                let present = quote! { mpstruct.#name.is_some() };
+                store_once(name, present, self.opts.duplicate, store)
+            }
+            Kind::Repeated(_) => {
+                let parse = self.deferred();
+                quote! {{
+                    let f = #parse;
+                    mpstruct.#name.push(f);
+                }}
+            }
+            // Rest of the multipart goes to the handler
+            Kind::Stream => quote! {{
+                mpstruct.#name = Some(awmpde::FieldStream::new(field, mp));
+                break;
+            }},
+        }
+    }
+
+    /// Variant of the struct's part enum for this field.
+    fn part_variant(&self) -> TokenStream2 {
+        let variant = self.variant_ident();
+        let ty = match self.kind {
+            Kind::Single(ty) => quote! { #ty },
+            Kind::Optional(vty) | Kind::Repeated(vty) => quote! { #vty },
+            Kind::Stream => unreachable!("`FieldStream` has no part"),
+        };
+        quote! { #variant(#ty) }
+    }
+
+    /// Match arm of `FromMultipartPart::from_part` for this field.
+    fn part_arm(&self, part: &Ident) -> TokenStream2 {
+        let name = self.name;
+        let variant = self.variant_ident();
+        let parse = self.deferred();
+        quote! { stringify!(#name) => Ok(#part::#variant(#parse.await?)) }
+    }
+
+    /// Field name in PascalCase.
+    fn variant_ident(&self) -> Ident {
+        let name = self.name.to_string();
+        let mut variant = String::new();
+        for word in name.trim_start_matches("r#").split('_') {
+            let mut chars = word.chars();
+            if let Some(c) = chars.next() {
+                variant.extend(c.to_uppercase());
+                variant.extend(chars);
+            }
+        }
+        Ident::new(&variant, self.name.span())
+    }
+
+    /// Match arm storing a part, body-only fields are skipped in the query pass.
+    fn arm(&self) -> TokenStream2 {
+        let name = self.name;
+        let code = self.matched();
+        if self.opts.from_query && !matches!(self.kind, Kind::Stream) {
+            quote! { stringify!(#name) => #code }
+        } else {
+            quote! { stringify!(#name) if !query => #code }
+        }
+    }
+
+    /// Moves the value parsed from the query string into `mpstruct` according to `precedence`.
+    fn merge(&self, precedence: Precedence) -> TokenStream2 {
+        let name = self.name;
+        let present = |s: TokenStream2| match self.kind {
+            Kind::Single(_) => quote! { #s.#name.is_ok() },
+            Kind::Optional(_) => quote! { #s.#name.is_some() },
+            Kind::Repeated(_) => quote! { !#s.#name.is_empty() },
+            Kind::Stream => unreachable!("`FieldStream` is never taken from the query"),
+        };
+        let in_body = present(quote! { mpstruct });
+        let in_query = present(quote! { qstruct });
+
+        match precedence {
+            Precedence::Body => quote! {
+                if !(#in_body) && #in_query {
+                    mpstruct.#name = qstruct.#name;
+                }
+            },
+            Precedence::Query => quote! {
+                if #in_query {
+                    mpstruct.#name = qstruct.#name;
+                }
+            },
+            Precedence::Error => quote! {
+                if #in_query {
+                    if #in_body {
+                        return Err(awmpde::Error::DuplicateFieldError(stringify!(#name)));
+                    }
+                    mpstruct.#name = qstruct.#name;
+                }
+            },
+        }
+    }
+
+    fn finish(&self) -> TokenStream2 {
+        let name = self.name;
+        match self.kind {
+            Kind::Single(_) => quote! { #name: mpstruct.#name?.await? },
+            Kind::Optional(_) => quote! {
+                #name: match mpstruct.#name {
+                    Some(f) => Some(f.await?),
+                    None => None,
+                }
+            },
+            Kind::Repeated(_) => quote! {
+                #name: awmpde::futures::future::try_join_all(mpstruct.#name).await?
+            },
+            Kind::Stream => quote! {
+                #name: mpstruct.#name.ok_or(awmpde::Error::FieldError(stringify!(#name)))?
+            },
+        }
+    }
+}
+
+/// Wraps `store` for single-valued fields so that a repeated part is handled
+/// according to the field's duplicate policy.
+fn store_once(
+    name: &Ident,
+    present: TokenStream2,
+    duplicate: Duplicate,
+    store: TokenStream2,
+) -> TokenStream2 {
+    let drain = quote! {
+        drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
+    };
+    match duplicate {
+        Duplicate::Last => store,
+        Duplicate::First => quote! {
+            if #present { #drain } else #store
+        },
+        Duplicate::Error => quote! {
+            if #present {
+                e = Some(awmpde::Error::DuplicateFieldError(stringify!(#name)));
+                #drain
+            } else #store
+        },
+    }
+}
+
 pub fn derive_actix_multipart(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let ident = &ast.ident;
@@ -39,141 +353,200 @@ pub fn derive_actix_multipart(input: TokenStream) -> TokenStream {
         unimplemented!();
     };
 
-    let struct_fields = fields.iter().map(|f| {
-        let name = f.ident.as_ref().unwrap();
-        let ty = &f.ty;
-
-        if let Type::Path(ref typ) = ty {
-            if let Some(vty) = get_struct_arg("Option", typ) {
-                return quote! { #name: std::option::Option<#vty> };
-            }
-            if let Some(vty) = get_struct_arg("Vec", typ) {
-                return quote! { #name: std::vec::Vec<#vty> };
-            }
+    let container = match ContainerOpts::from_attrs(&ast.attrs) {
+        Ok(opts) => opts,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let mut fields = match fields
+        .iter()
+        .map(MpField::new)
+        .collect::<syn::Result<Vec<_>>>()
+    {
+        Ok(fields) => fields,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if container.query {
+        for f in &mut fields {
+            f.opts.from_query = true;
         }
-        quote! { #name: std::result::Result<#ty, awmpde::Error> }
-    });
-
-    let struct_field_values = fields.iter().map(|f| {
-        let name = f.ident.as_ref().unwrap();
-        let ty = &f.ty;
+    }
+    let streams = fields
+        .iter()
+        .filter(|f| matches!(f.kind, Kind::Stream))
+        .count();
+    if streams > 1 || streams == 1 && !matches!(fields.last().unwrap().kind, Kind::Stream) {
+        let f = fields
+            .iter()
+            .find(|f| matches!(f.kind, Kind::Stream))
+            .unwrap();
+        return syn::Error::new_spanned(f.name, "`FieldStream` must be the last field")
+            .to_compile_error()
+            .into();
+    }
+    if container.stream && streams > 0 {
+        let f = fields.last().unwrap();
+        return syn::Error::new_spanned(
+            f.name,
+            "`#[awmpde(stream)]` can't be used with a `FieldStream` field",
+        )
+        .to_compile_error()
+        .into();
+    }
 
-        if let Type::Path(ref typ) = ty {
-            if get_struct_arg("Option", typ).is_some() {
-                return quote! { #name: std::option::Option::None };
-            }
-            if get_struct_arg("Vec", typ).is_some() {
-                return quote! { #name: std::vec::Vec::new() };
-            }
-        }
-        quote! {
-            #name: std::result::Result::Err(awmpde::Error::FieldError(stringify!(#name)))
-        }
-    });
+    let struct_fields = fields.iter().map(MpField::struct_field);
+    let struct_field_values = fields.iter().map(MpField::initial_value);
+    let arms = fields.iter().map(MpField::arm);
 
-    let matched = fields
+    let query_fields = fields
         .iter()
-        .map(|f| {
-            let name = f.ident.as_ref().unwrap();
-            let ty = &f.ty;
-
-            let code = if from_json_attr(f).is_some() {
-                quote! {
-                    <awmpde::Json<#ty> as awmpde::FromField>::from_field(field)
-                        .await?.0
-                }
-            } else if let Type::Path(ref typ) = ty {
-                if let Some(vty) = get_struct_arg("Vec", typ) {
-                    let code = quote! {{
-                        let f =
-                            <#vty as awmpde::FromField>::from_field(field).await?;
-                        mpstruct.#name.push(f);
-                    }};
-                    return (name, code);
-                } else if let Some(vty) = get_struct_arg("Option", typ) {
-                    let code = quote! {{
-                        let f =
-                            <#vty as awmpde::FromField>::from_field(field).await?;
-                        mpstruct.#name = Some(f);
-                    }};
-                    return (name, code);
-                } else {
-                    quote! {
-                        <#ty as awmpde::FromField>::from_field(field).await?
-                    }
-                }
-            } else {
-                unimplemented!()
+        .filter(|f| f.opts.from_query && !matches!(f.kind, Kind::Stream))
+        .collect::<Vec<_>>();
+    let fill = if query_fields.is_empty() {
+        quote! { fill(mp, &mut mpstruct, &config, false).await?; }
+    } else {
+        let initial = fields.iter().map(MpField::initial_value);
+        let merge = query_fields.iter().map(|f| f.merge(container.precedence));
+        quote! {
+            let mut qstruct = MPStructure {
+                #(#initial,)*
             };
+            fill(awmpde::query_multipart(&req)?, &mut qstruct, &config, true).await?;
+            fill(mp, &mut mpstruct, &config, false).await?;
+            #(#merge)*
+        }
+    };
 
-            let code = quote! {{ mpstruct.#name = Ok(#code); }};
+    let parts = if container.stream {
+        part_enum(&ast, &fields)
+    } else {
+        quote! {}
+    };
 
-            (name, code)
-        })
-        .map(|(name, code)| quote! { stringify!(#name) => #code });
-    let fields = fields.iter().map(|f| {
-        let name = &f.ident;
-        let ty = &f.ty;
-        if let Type::Path(ref typ) = ty {
-            if get_struct_arg("Option", typ).is_some() || get_struct_arg("Vec", typ).is_some() {
-                return quote! { #name: mpstruct.#name };
-            }
-        }
-        quote! { #name: mpstruct.#name? }
-    });
+    let fields = fields.iter().map(MpField::finish);
 
     let expanded = quote! {
-        impl<'a> awmpde::FromMultipart<'a> for #ident {
+        impl awmpde::FromMultipart for #ident {
             type Error = awmpde::Error;
             type Future = awmpde::futures::future::LocalBoxFuture<
-               'a, std::result::Result<Self, awmpde::Error>
+               'static, std::result::Result<Self, awmpde::Error>
             >;
 
             #[inline]
             fn from_multipart(
-                mut mp: awmpde::actix_multipart::Multipart,
+                req: &awmpde::actix_web::HttpRequest,
+                mp: awmpde::actix_multipart::Multipart,
             ) -> Self::Future {
                 use awmpde::futures::{TryStreamExt, future::FutureExt};
 
+                let req = req.clone();
                 async move {
                     struct MPStructure {
                         #(#struct_fields,)*
                     };
 
-                    let mut e: std::option::Option<awmpde::Error> = None;
+                    /// Stores parts of `mp` in `mpstruct`, in the query pass unknown names
+                    /// are ignored.
+                    async fn fill(
+                        mut mp: awmpde::actix_multipart::Multipart,
+                        mpstruct: &mut MPStructure,
+                        config: &awmpde::FieldConfig,
+                        query: bool,
+                    ) -> std::result::Result<(), awmpde::Error> {
+                        let mut e: std::option::Option<awmpde::Error> = None;
 
-                    let mut mpstruct = MPStructure {
-                        #(#struct_field_values,)*
-                    };
+                        while let Ok(Some(field)) = mp.try_next().await {
+                            let mut disp = awmpde::get_content_disposition(&field);
+                            let name = disp.remove("name").unwrap_or_default();
 
-                    while let Ok(Some(field)) = mp.try_next().await {
-                        let mut disp = awmpde::get_content_disposition(&field);
-                        let name = disp.remove("name").unwrap();
+                            if e.is_some() {
+                                drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
+                                continue;
+                            }
 
-                        if e.is_some() {
-                            drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
-                            continue;
+                            match &name[..] {
+                                #(#arms,)*
+                                _ if query => {
+                                    drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
+                                },
+                                _ => {
+                                    e = Some(awmpde::Error::NoFieldError(name.to_string()));
+                                    drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
+                                },
+                            }
                         }
 
-                        match &name[..] {
-                            #(#matched,)*
-                            _ => {
-                                e = Some(awmpde::Error::NoFieldError(name.to_string()));
-                                drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
-                            },
+                        match e {
+                            Some(e) => Err(e),
+                            None => Ok(()),
                         }
                     }
 
-                    if let Some(e) = e {
-                        Err(e)
-                    } else {
-                        Ok(Self{ #(#fields,)* })
-                    }
+                    let config = req
+                        .app_data::<awmpde::FieldConfig>()
+                        .cloned()
+                        .unwrap_or_default();
+                    let mut mpstruct = MPStructure {
+                        #(#struct_field_values,)*
+                    };
+                    #fill
+
+                    Ok(Self{ #(#fields,)* })
                 }
                 .boxed_local()
             }
         }
+
+        #parts
     };
 
     expanded.into()
 }
+
+/// `<Struct>Part` enum with a variant per field and its `FromMultipartPart` implementation.
+fn part_enum(ast: &DeriveInput, fields: &[MpField]) -> TokenStream2 {
+    let ident = &ast.ident;
+    let vis = &ast.vis;
+    let part = Ident::new(&format!("{}Part", ident), ident.span());
+    let doc = format!("Part of [`{}`], see `awmpde::MultipartStream`.", ident);
+
+    let variants = fields.iter().map(MpField::part_variant);
+    let arms = fields.iter().map(|f| f.part_arm(&part));
+
+    quote! {
+        #[doc = #doc]
+        // Only used with `MultipartStream`
+        #[allow(dead_code)]
+        #vis enum #part {
+            #(#variants,)*
+        }
+
+        impl awmpde::FromMultipartPart for #ident {
+            type Part = #part;
+
+            fn from_part(
+                field: awmpde::actix_multipart::Field,
+                config: &awmpde::FieldConfig,
+            ) -> awmpde::futures::future::LocalBoxFuture<
+                'static, std::result::Result<#part, awmpde::Error>
+            > {
+                use awmpde::futures::future::FutureExt;
+
+                let config = config.clone();
+                async move {
+                    let config = &config;
+                    let mut disp = awmpde::get_content_disposition(&field);
+                    let name = disp.remove("name").unwrap_or_default();
+
+                    match &name[..] {
+                        #(#arms,)*
+                        _ => {
+                            drop(<std::vec::Vec<u8> as awmpde::FromField>::from_field(field).await?);
+                            Err(awmpde::Error::NoFieldError(name.to_string()))
+                        },
+                    }
+                }
+                .boxed_local()
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use crate::opts::{awmpde_metas, lit_str};
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DataEnum, DataStruct, DeriveInput, Fields, Meta, Result};
+
+#[derive(Clone, Copy)]
+enum RenameAll {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+}
+
+impl RenameAll {
+    fn from_str(s: &str) -> Option<Self> {
+        Some(match s {
+            "lowercase" => Self::Lower,
+            "UPPERCASE" => Self::Upper,
+            "PascalCase" => Self::Pascal,
+            "camelCase" => Self::Camel,
+            "snake_case" => Self::Snake,
+            "SCREAMING_SNAKE_CASE" => Self::ScreamingSnake,
+            "kebab-case" => Self::Kebab,
+            _ => return None,
+        })
+    }
+
+    /// Converts a PascalCase variant name to this case.
+    fn apply(self, variant: &str) -> String {
+        let snake = || {
+            let mut out = String::new();
+            for (i, c) in variant.char_indices() {
+                if i > 0 && c.is_uppercase() {
+                    out.push('_');
+                }
+                out.extend(c.to_lowercase());
+            }
+            out
+        };
+
+        match self {
+            Self::Lower => variant.to_lowercase(),
+            Self::Upper => variant.to_uppercase(),
+            Self::Pascal => variant.to_string(),
+            Self::Camel => {
+                let mut chars = variant.chars();
+                chars
+                    .next()
+                    .map(|c| c.to_lowercase().chain(chars).collect())
+                    .unwrap_or_default()
+            }
+            Self::Snake => snake(),
+            Self::ScreamingSnake => snake().to_uppercase(),
+            Self::Kebab => snake().replace('_', "-"),
+        }
+    }
+}
+
+#[derive(Default)]
+struct EnumOpts {
+    rename_all: Option<RenameAll>,
+    case_insensitive: bool,
+}
+
+impl EnumOpts {
+    fn from_attrs(attrs: &[syn::Attribute]) -> Result<Self> {
+        let mut opts = Self::default();
+
+        for meta in awmpde_metas(attrs)? {
+            match meta {
+                Meta::NameValue(nv) if nv.path.is_ident("rename_all") => {
+                    opts.rename_all = Some(
+                        RenameAll::from_str(&lit_str(&nv.lit)?)
+                            .ok_or_else(|| syn::Error::new_spanned(&nv.lit, "unknown case"))?,
+                    )
+                }
+                Meta::Path(path) if path.is_ident("case_insensitive") => {
+                    opts.case_insensitive = true
+                }
+                meta => return Err(syn::Error::new_spanned(meta, "unknown awmpde option")),
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+fn variant_rename(attrs: &[syn::Attribute]) -> Result<Option<String>> {
+    let mut rename = None;
+
+    for meta in awmpde_metas(attrs)? {
+        match meta {
+            Meta::NameValue(nv) if nv.path.is_ident("rename") => rename = Some(lit_str(&nv.lit)?),
+            meta => return Err(syn::Error::new_spanned(meta, "unknown awmpde option")),
+        }
+    }
+
+    Ok(rename)
+}
+
+fn newtype(ast: &DeriveInput, fields: &Fields) -> Result<TokenStream2> {
+    let ident = &ast.ident;
+    let (field, ctor) = match fields {
+        Fields::Unnamed(f) if f.unnamed.len() == 1 => (&f.unnamed[0], quote! { Self }),
+        Fields::Named(f) if f.named.len() == 1 => {
+            let name = &f.named[0].ident;
+            (&f.named[0], quote! { |#name| Self { #name } })
+        }
+        _ => {
+            return Err(syn::Error::new_spanned(
+                ident,
+                "FromField can only be derived for structs with exactly one field",
+            ))
+        }
+    };
+    let ty = &field.ty;
+
+    let mut generics = ast.generics.clone();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote! { #ty: awmpde::FromField + 'static });
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics awmpde::FromField for #ident #ty_generics #where_clause {
+            type Error = <#ty as awmpde::FromField>::Error;
+            type Future = awmpde::futures::future::LocalBoxFuture<
+                'static, std::result::Result<Self, Self::Error>
+            >;
+
+            #[inline]
+            fn from_field(field: awmpde::actix_multipart::Field) -> Self::Future {
+                use awmpde::futures::future::FutureExt;
+
+                <#ty as awmpde::FromField>::from_field(field)
+                    .map(|res| res.map(#ctor))
+                    .boxed_local()
+            }
+
+            fn from_field_deferred(
+                field: awmpde::actix_multipart::Field,
+                config: &awmpde::FieldConfig,
+            ) -> awmpde::DeferredRead<Self, Self::Error>
+            where
+                Self: 'static,
+                Self::Error: 'static,
+            {
+                use awmpde::futures::future::FutureExt;
+
+                <#ty as awmpde::FromField>::from_field_deferred(field, config)
+                    .map(|res| res.map(|rest| rest.map(|res| res.map(#ctor)).boxed_local()))
+                    .boxed_local()
+            }
+        }
+    })
+}
+
+fn unit_enum(ast: &DeriveInput, data: &DataEnum) -> Result<TokenStream2> {
+    let ident = &ast.ident;
+    let opts = EnumOpts::from_attrs(&ast.attrs)?;
+
+    let mut arms = Vec::new();
+    for v in &data.variants {
+        if !matches!(v.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                v,
+                "FromField can only be derived for enums with unit variants",
+            ));
+        }
+
+        let vident = &v.ident;
+        let mut value = match variant_rename(&v.attrs)? {
+            Some(rename) => rename,
+            None => match opts.rename_all {
+                Some(rename_all) => rename_all.apply(&vident.to_string()),
+                None => vident.to_string(),
+            },
+        };
+        if opts.case_insensitive {
+            value = value.to_lowercase();
+        }
+
+        arms.push(quote! { #value => Ok(Self::#vident) });
+    }
+
+    let value = if opts.case_insensitive {
+        quote! { &s.to_lowercase()[..] }
+    } else {
+        quote! { &s[..] }
+    };
+
+    Ok(quote! {
+        impl awmpde::FromField for #ident {
+            type Error = awmpde::Error;
+            type Future = awmpde::futures::future::LocalBoxFuture<
+                'static, std::result::Result<Self, awmpde::Error>
+            >;
+
+            #[inline]
+            fn from_field(field: awmpde::actix_multipart::Field) -> Self::Future {
+                use awmpde::futures::future::FutureExt;
+
+                async move {
+                    let s = <std::string::String as awmpde::FromField>::from_field(field).await?;
+                    match #value {
+                        #(#arms,)*
+                        _ => Err(awmpde::Error::UnknownVariantError(s)),
+                    }
+                }
+                .boxed_local()
+            }
+        }
+    })
+}
+
+pub fn derive_from_field(input: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(input as DeriveInput);
+
+    let out = match &ast.data {
+        Data::Struct(DataStruct { fields, .. }) => newtype(&ast, fields),
+        Data::Enum(data) => unit_enum(&ast, data),
+        Data::Union(_) => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "FromField can't be derived for unions",
+        )),
+    };
+
+    out.unwrap_or_else(|e| e.to_compile_error()).into()
+}
@@ -1,16 +1,99 @@
 #![recursion_limit = "128"]
 
-mod attrib;
 mod derive;
+mod from_field;
+mod opts;
 
 use proc_macro::TokenStream;
 
+/// Does nothing, kept for compatibility.
+///
+/// `FormOrMultipart` used to be unwrapped by this attribute, now it's an extractor by itself.
+#[deprecated(
+    since = "0.8.0",
+    note = "`FormOrMultipart` is an extractor now, remove the attribute"
+)]
 #[proc_macro_attribute]
-pub fn form_or_multipart_unwrap(args: TokenStream, input: TokenStream) -> TokenStream {
-    attrib::form_or_multipart_unwrap(args, input)
+pub fn form_or_multipart_unwrap(_args: TokenStream, input: TokenStream) -> TokenStream {
+    input
 }
 
-#[proc_macro_derive(FromActixMultipart, attributes(serde_json))]
+/// Derives `FromMultipart` for a struct with named fields.
+///
+/// Struct attributes:
+/// - `#[awmpde(query)]` lets every field also be taken from the request's query string.
+/// - `#[awmpde(query_precedence = "body" | "query" | "error")]` chooses which value is kept
+///   when a field is both in the query string and in the body. Defaults to `"body"`.
+/// - `#[awmpde(stream)]` also generates `<Struct>Part` enum for `awmpde::MultipartStream`, see
+///   below.
+///
+/// Query parameters are parsed the same way as text parts. Parameters not matching a field
+/// are ignored.
+///
+/// Field attributes:
+/// - `#[awmpde(from_query)]` lets this field also be taken from the query string.
+/// - `#[awmpde(format = "...")]` decodes the part with one of the `awmpde` format wrappers:
+///   `"json"`, `"toml"`, `"yaml"`, `"xml"`, `"msgpack"`, `"cbor"` or `"protobuf"`. All but json
+///   need the crate feature of the same name.
+/// - `#[awmpde(limit = N)]` sets the most bytes of a `"protobuf"` part, 1 MiB by default.
+/// - `#[serde_json]` is the same as `#[awmpde(format = "json")]`.
+/// - `#[awmpde(duplicate = "first" | "last" | "error")]` chooses what happens when a
+///   single-valued field is sent more than once. Defaults to `"error"`.
+/// - `#[awmpde(max_width = N, max_height = N, max_pixels = N, max_alloc = N)]` override
+///   limits of `awmpde::images::ImageConfig` for an image field, `max_frames = N` and
+///   `max_total_pixels = N` limit `awmpde::images::Frames`.
+/// - `#[awmpde(reject_animated)]` fails on animated images.
+/// - `#[awmpde(image_formats = "png,jpeg,webp")]` accepts only images of these formats,
+///   detected from the image's magic bytes. QOI isn't supported.
+/// - `#[awmpde(auto_orient)]` rotates a decoded image upright by its EXIF orientation, and
+///   `#[awmpde(strip_gps)]` blanks GPS tags of kept JPEG bytes. Both need the `exif` feature.
+/// - `#[awmpde(resize(max_w = N, max_h = N, filter = "lanczos3", mode = "fit"))]` sets the
+///   size of `awmpde::images::Resized` and `Thumbnail` fields. `filter` is one of `"nearest"`,
+///   `"triangle"` (default), `"catmullrom"`, `"gaussian"` or `"lanczos3"`, `mode` one of
+///   `"fit"` (default), `"fill"` or `"exact"`.
+/// - `#[awmpde(decoder = "image" | "mozjpeg" | "zune-jpeg")]` decodes an image field with this
+///   backend and its default options. The last two need the crate feature of the same name.
+/// - `#[awmpde(tensor(width = 224, height = 224, layout = "chw", mean = "0.485, 0.456, 0.406",
+///   std = "0.229, 0.224, 0.225", letterbox))]` shapes an `awmpde::images::Tensor` field, all
+///   options are optional. Needs the `ndarray` feature.
+/// - `#[awmpde(with = "module")]` parses the part with
+///   `module::from_field(field: Field) -> impl Future<Output = Result<T, E>>`.
+/// - `#[awmpde(parse_with = "path")]` parses the part's bytes with
+///   `fn(Vec<u8>) -> Result<T, E>`.
+///
+/// In both cases `E: Into<actix_web::Error>`. For `Option<T>` fields the parser returns `T`,
+/// otherwise it returns the whole field type, so a `Vec` field is filled from a single part.
+///
+/// A field of type `awmpde::FieldStream` must be the last one. Parts before it are parsed as
+/// usual, then its part is handed over unread, so the handler can stream it without buffering.
+///
+/// With `#[awmpde(stream)]` also generates `<Struct>Part` enum with a variant per field, named
+/// in PascalCase, for `awmpde::MultipartStream`. Variants hold a single item of the field, so
+/// `Option<T>` and `Vec<T>` fields give `T`. Parts are yielded as sent, so `duplicate` and the
+/// query options don't apply to the stream. Can't be used on structs with a `FieldStream`.
+///
+/// Formats decode a single item of the field, so `Vec<T>` collects every part with the
+/// field's name. Use `awmpde::Json<Vec<T>>` to decode a whole array from one part, which json
+/// bodies of `FormOrMultipart` can't fill as they give a part per array item.
+#[proc_macro_derive(FromActixMultipart, attributes(serde_json, awmpde))]
 pub fn derive_actix_multipart(input: TokenStream) -> TokenStream {
     derive::derive_actix_multipart(input)
 }
+
+/// Derives `FromField` for newtypes and enums with unit variants.
+///
+/// A newtype (struct with a single field) is parsed as its inner type. An enum is parsed from
+/// the part's text matched against variant names.
+///
+/// Enum attributes:
+/// - `#[awmpde(rename_all = "...")]` renames all variants, accepts `"lowercase"`,
+///   `"UPPERCASE"`, `"PascalCase"`, `"camelCase"`, `"snake_case"`, `"SCREAMING_SNAKE_CASE"`
+///   and `"kebab-case"`.
+/// - `#[awmpde(case_insensitive)]` ignores case of the value.
+///
+/// Variant attributes:
+/// - `#[awmpde(rename = "name")]` matches the variant against `name`.
+#[proc_macro_derive(FromField, attributes(awmpde))]
+pub fn derive_from_field(input: TokenStream) -> TokenStream {
+    from_field::derive_from_field(input)
+}
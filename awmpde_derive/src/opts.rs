@@ -0,0 +1,430 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{Attribute, Ident, Lit, Meta, MetaList, NestedMeta, Path, Result};
+
+/// What to do when a single-valued field is sent more than once.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Duplicate {
+    First,
+    Last,
+    Error,
+}
+
+/// Which value wins when a field is sent both in the query string and in the body.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Precedence {
+    Body,
+    Query,
+    Error,
+}
+
+/// Wrapper types decoding a part with `#[awmpde(format = "...")]`.
+const FORMATS: &[(&str, &str)] = &[
+    ("json", "Json"),
+    ("toml", "Toml"),
+    ("yaml", "Yaml"),
+    ("xml", "Xml"),
+    ("msgpack", "MsgPack"),
+    ("cbor", "Cbor"),
+    ("protobuf", "Protobuf"),
+];
+
+/// Limits of `ImageConfig` settable per field.
+const IMAGE_LIMITS: &[&str] = &[
+    "max_width",
+    "max_height",
+    "max_pixels",
+    "max_alloc",
+    "max_frames",
+    "max_total_pixels",
+];
+
+/// Switches of `ImageConfig` settable per field, the last two with `exif` feature.
+const IMAGE_FLAGS: &[&str] = &["reject_animated", "auto_orient", "strip_gps"];
+
+/// Names of `image::ImageFormat` variants for `#[awmpde(image_formats = "...")]`.
+const IMAGE_FORMATS: &[(&str, &str)] = &[
+    ("png", "Png"),
+    ("jpeg", "Jpeg"),
+    ("jpg", "Jpeg"),
+    ("gif", "Gif"),
+    ("webp", "WebP"),
+    ("pnm", "Pnm"),
+    ("tiff", "Tiff"),
+    ("tga", "Tga"),
+    ("dds", "Dds"),
+    ("bmp", "Bmp"),
+    ("ico", "Ico"),
+    ("hdr", "Hdr"),
+    ("farbfeld", "Farbfeld"),
+    ("avif", "Avif"),
+];
+
+fn image_formats(lit: &Lit) -> Result<Vec<Ident>> {
+    lit_str(lit)?
+        .split(',')
+        .map(|name| {
+            let name = name.trim().to_lowercase();
+            if name == "qoi" {
+                return Err(syn::Error::new_spanned(
+                    lit,
+                    "QOI images aren't supported, `image` 0.23 can't decode them",
+                ));
+            }
+            IMAGE_FORMATS
+                .iter()
+                .find(|(format, _)| *format == name)
+                .map(|(_, variant)| Ident::new(variant, lit.span()))
+                .ok_or_else(|| {
+                    syn::Error::new_spanned(lit, format!("unknown image format `{}`", name))
+                })
+        })
+        .collect()
+}
+
+/// Options parsed from `#[awmpde(...)]` field attributes.
+pub struct FieldOpts {
+    pub duplicate: Duplicate,
+    /// Module with `async fn from_field(Field) -> Result<T, E>`.
+    pub with: Option<Path>,
+    /// Function `fn(Vec<u8>) -> Result<T, E>` applied to the part's bytes.
+    pub parse_with: Option<Path>,
+    /// Name of the `awmpde` wrapper type decoding the part.
+    pub format: Option<Ident>,
+    /// Most bytes of a part decoded by `format`, its `LIMIT` parameter.
+    pub limit: Option<Lit>,
+    /// Field may also be taken from the query string.
+    pub from_query: bool,
+    /// `ImageConfig` builder calls overriding the app's config.
+    pub image: Vec<TokenStream>,
+}
+
+impl Default for FieldOpts {
+    fn default() -> Self {
+        Self {
+            duplicate: Duplicate::Error,
+            with: None,
+            parse_with: None,
+            format: None,
+            limit: None,
+            from_query: false,
+            image: Vec::new(),
+        }
+    }
+}
+
+pub fn lit_str(lit: &Lit) -> Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(syn::Error::new_spanned(lit, "expected string literal")),
+    }
+}
+
+fn lit_path(lit: &Lit) -> Result<Path> {
+    match lit {
+        Lit::Str(s) => s.parse(),
+        _ => Err(syn::Error::new_spanned(
+            lit,
+            "expected path in string literal",
+        )),
+    }
+}
+
+fn format_wrapper(lit: &Lit) -> Result<Ident> {
+    let name = lit_str(lit)?;
+    FORMATS
+        .iter()
+        .find(|(format, _)| *format == name)
+        .map(|(_, wrapper)| Ident::new(wrapper, lit.span()))
+        .ok_or_else(|| {
+            let known = FORMATS.iter().map(|(f, _)| *f).collect::<Vec<_>>();
+            syn::Error::new_spanned(lit, format!("unknown format, expected one of {:?}", known))
+        })
+}
+
+/// Variants of `FilterType` and `ResizeMode` for `#[awmpde(resize(...))]`.
+const RESIZE_FILTERS: &[(&str, &str)] = &[
+    ("nearest", "Nearest"),
+    ("triangle", "Triangle"),
+    ("catmullrom", "CatmullRom"),
+    ("gaussian", "Gaussian"),
+    ("lanczos3", "Lanczos3"),
+];
+const RESIZE_MODES: &[(&str, &str)] = &[("fit", "Fit"), ("fill", "Fill"), ("exact", "Exact")];
+
+/// Image decoder backends for `#[awmpde(decoder = "...")]`, each behind its crate feature.
+const DECODERS: &[(&str, &str)] = &[
+    ("image", "ImageRs"),
+    ("mozjpeg", "Mozjpeg"),
+    ("zune-jpeg", "ZuneJpeg"),
+];
+
+fn lit_variant(lit: &Lit, variants: &[(&str, &str)]) -> Result<Ident> {
+    let name = lit_str(lit)?;
+    variants
+        .iter()
+        .find(|(v, _)| *v == name)
+        .map(|(_, variant)| Ident::new(variant, lit.span()))
+        .ok_or_else(|| {
+            let known = variants.iter().map(|(v, _)| *v).collect::<Vec<_>>();
+            syn::Error::new_spanned(lit, format!("expected one of {:?}", known))
+        })
+}
+
+/// `Resize` built from `resize(max_w = N, max_h = N, filter = "...", mode = "...")`.
+fn resize(list: &MetaList) -> Result<TokenStream> {
+    let (mut width, mut height, mut calls) = (None, None, Vec::new());
+
+    for nested in &list.nested {
+        match nested {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("max_w") => {
+                width = Some(lit_size(&nv.lit)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("max_h") => {
+                height = Some(lit_size(&nv.lit)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("filter") => {
+                let filter = lit_variant(&nv.lit, RESIZE_FILTERS)?;
+                calls.push(quote! { .filter(awmpde::images::FilterType::#filter) })
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("mode") => {
+                let mode = lit_variant(&nv.lit, RESIZE_MODES)?;
+                calls.push(quote! { .mode(awmpde::images::ResizeMode::#mode) })
+            }
+            nested => return Err(syn::Error::new_spanned(nested, "unknown resize option")),
+        }
+    }
+
+    match (width, height) {
+        (Some(w), Some(h)) => Ok(quote! {
+            .resize(awmpde::images::Resize::new(#w, #h) #(#calls)*)
+        }),
+        _ => Err(syn::Error::new_spanned(
+            list,
+            "resize needs `max_w` and `max_h`",
+        )),
+    }
+}
+
+const TENSOR_LAYOUTS: &[(&str, &str)] = &[("hwc", "Hwc"), ("chw", "Chw")];
+
+/// Three floats of a `"r, g, b"` string.
+fn lit_rgb(lit: &Lit) -> Result<TokenStream> {
+    let values = lit_str(lit)?
+        .split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .ok()
+        .filter(|v| v.len() == 3)
+        .ok_or_else(|| syn::Error::new_spanned(lit, "expected three comma separated numbers"))?;
+    Ok(quote! { [#(#values),*] })
+}
+
+/// `TensorConfig` built from
+/// `tensor(width = N, height = N, layout = "...", mean = "...", std = "...", letterbox)`.
+fn tensor(list: &MetaList) -> Result<TokenStream> {
+    let (mut width, mut height, mut calls) = (None, None, Vec::new());
+
+    for nested in &list.nested {
+        match nested {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("width") => {
+                width = Some(lit_size(&nv.lit)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("height") => {
+                height = Some(lit_size(&nv.lit)?)
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("layout") => {
+                let layout = lit_variant(&nv.lit, TENSOR_LAYOUTS)?;
+                calls.push(quote! { .layout(awmpde::images::Layout::#layout) })
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("mean") => {
+                let mean = lit_rgb(&nv.lit)?;
+                calls.push(quote! { .mean(#mean) })
+            }
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("std") => {
+                let std = lit_rgb(&nv.lit)?;
+                calls.push(quote! { .std(#std) })
+            }
+            NestedMeta::Meta(Meta::Path(path)) if path.is_ident("letterbox") => {
+                calls.push(quote! { .letterbox() })
+            }
+            nested => return Err(syn::Error::new_spanned(nested, "unknown tensor option")),
+        }
+    }
+
+    match (width, height) {
+        (Some(w), Some(h)) => calls.push(quote! { .size(#w, #h) }),
+        (None, None) => {}
+        _ => {
+            return Err(syn::Error::new_spanned(
+                list,
+                "tensor needs both `width` and `height`",
+            ))
+        }
+    }
+
+    Ok(quote! {
+        .tensor(awmpde::images::TensorConfig::default() #(#calls)*)
+    })
+}
+
+fn lit_int(lit: &Lit) -> Result<&Lit> {
+    match lit {
+        Lit::Int(_) => Ok(lit),
+        _ => Err(syn::Error::new_spanned(lit, "expected integer")),
+    }
+}
+
+/// Integer literal of a size, which can't be zero.
+fn lit_size(lit: &Lit) -> Result<&Lit> {
+    match lit {
+        Lit::Int(int) if int.base10_digits().parse::<u64>().ok() == Some(0) => {
+            Err(syn::Error::new_spanned(lit, "size can't be zero"))
+        }
+        _ => lit_int(lit),
+    }
+}
+
+/// Collects the options of all `#[awmpde(...)]` attributes.
+pub fn awmpde_metas(attrs: &[Attribute]) -> Result<Vec<Meta>> {
+    let mut out = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("awmpde")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Meta(meta) => out.push(meta),
+                        NestedMeta::Lit(lit) => {
+                            return Err(syn::Error::new_spanned(lit, "expected awmpde option"))
+                        }
+                    }
+                }
+            }
+            meta => return Err(syn::Error::new_spanned(meta, "expected #[awmpde(...)]")),
+        }
+    }
+    Ok(out)
+}
+
+impl FieldOpts {
+    pub fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut opts = Self::default();
+
+        for meta in awmpde_metas(attrs)? {
+            match meta {
+                Meta::NameValue(nv) if nv.path.is_ident("duplicate") => {
+                    opts.duplicate = match &lit_str(&nv.lit)?[..] {
+                        "first" => Duplicate::First,
+                        "last" => Duplicate::Last,
+                        "error" => Duplicate::Error,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                nv.lit,
+                                "expected one of \"first\", \"last\" or \"error\"",
+                            ))
+                        }
+                    }
+                }
+                Meta::NameValue(nv) if nv.path.is_ident("with") => {
+                    opts.with = Some(lit_path(&nv.lit)?)
+                }
+                Meta::NameValue(nv) if nv.path.is_ident("parse_with") => {
+                    opts.parse_with = Some(lit_path(&nv.lit)?)
+                }
+                Meta::NameValue(nv) if nv.path.is_ident("format") => {
+                    opts.format = Some(format_wrapper(&nv.lit)?)
+                }
+                Meta::NameValue(nv) if nv.path.is_ident("limit") => {
+                    opts.limit = Some(lit_size(&nv.lit)?.clone())
+                }
+                Meta::Path(path) if path.is_ident("from_query") => opts.from_query = true,
+                Meta::NameValue(nv) if IMAGE_LIMITS.iter().any(|l| nv.path.is_ident(l)) => {
+                    let (method, lit) = (nv.path.get_ident().unwrap(), lit_int(&nv.lit)?);
+                    opts.image.push(quote! { .#method(#lit) })
+                }
+                Meta::List(list) if list.path.is_ident("resize") => opts.image.push(resize(&list)?),
+                Meta::List(list) if list.path.is_ident("tensor") => opts.image.push(tensor(&list)?),
+                Meta::NameValue(nv) if nv.path.is_ident("decoder") => {
+                    let decoder = lit_variant(&nv.lit, DECODERS)?;
+                    opts.image
+                        .push(quote! { .decoder(awmpde::images::#decoder::default()) })
+                }
+                Meta::Path(path) if IMAGE_FLAGS.iter().any(|f| path.is_ident(f)) => {
+                    let method = path.get_ident().unwrap();
+                    opts.image.push(quote! { .#method() })
+                }
+                Meta::NameValue(nv) if nv.path.is_ident("image_formats") => {
+                    let formats = image_formats(&nv.lit)?;
+                    opts.image.push(quote! {
+                        .allowed_formats(&[#(awmpde::images::ImageFormat::#formats),*])
+                    })
+                }
+                meta => return Err(syn::Error::new_spanned(meta, "unknown awmpde option")),
+            }
+        }
+
+        let parsers = [
+            opts.with.is_some(),
+            opts.parse_with.is_some(),
+            opts.format.is_some(),
+        ];
+        if parsers.iter().filter(|set| **set).count() > 1 {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`with`, `parse_with` and `format` are mutually exclusive",
+            ));
+        }
+        if let Some(limit) = &opts.limit {
+            if !matches!(&opts.format, Some(format) if format == "Protobuf") {
+                return Err(syn::Error::new_spanned(
+                    limit,
+                    "`limit` is only supported with `format = \"protobuf\"`",
+                ));
+            }
+        }
+
+        Ok(opts)
+    }
+}
+
+/// Options parsed from `#[awmpde(...)]` struct attributes.
+pub struct ContainerOpts {
+    /// All fields may be taken from the query string.
+    pub query: bool,
+    pub precedence: Precedence,
+    /// Generate `<Struct>Part` enum for `MultipartStream`.
+    pub stream: bool,
+}
+
+impl ContainerOpts {
+    pub fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut opts = Self {
+            query: false,
+            precedence: Precedence::Body,
+            stream: false,
+        };
+
+        for meta in awmpde_metas(attrs)? {
+            match meta {
+                Meta::Path(path) if path.is_ident("query") => opts.query = true,
+                Meta::Path(path) if path.is_ident("stream") => opts.stream = true,
+                Meta::NameValue(nv) if nv.path.is_ident("query_precedence") => {
+                    opts.precedence = match &lit_str(&nv.lit)?[..] {
+                        "body" => Precedence::Body,
+                        "query" => Precedence::Query,
+                        "error" => Precedence::Error,
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                nv.lit,
+                                "expected one of \"body\", \"query\" or \"error\"",
+                            ))
+                        }
+                    }
+                }
+                meta => return Err(syn::Error::new_spanned(meta, "unknown awmpde option")),
+            }
+        }
+
+        Ok(opts)
+    }
+}
@@ -21,6 +21,22 @@ impl FromField for Vec<u8> {
     }
 }
 
+/// Reads the whole field, failing with `Error::FieldTooLarge` once it's over `limit` bytes.
+pub async fn read_field_limited(
+    mut field: actix_multipart::Field,
+    limit: usize,
+) -> Result<Vec<u8>, Error> {
+    let mut vec: Vec<u8> = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(actix_web::Error::from)?;
+        if vec.len() + chunk.len() > limit {
+            return Err(Error::FieldTooLarge(limit));
+        }
+        vec.extend_from_slice(&chunk);
+    }
+    Ok(vec)
+}
+
 /// Type for wrapping any other field in order to get its name and mime.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct File<T> {
@@ -35,10 +51,57 @@ impl<T> File<T> {
     }
 }
 
+/// Deserializes as a struct with `name`, `mime` and `inner` fields, which
+/// [`de::from_multipart`](crate::de::from_multipart) fills from a file part.
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for File<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{self, MapAccess, Visitor};
+        use std::fmt;
+
+        struct FileVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: serde::Deserialize<'de>> Visitor<'de> for FileVisitor<T> {
+            type Value = File<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("file part")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<File<T>, A::Error> {
+                let (mut name, mut mime, mut inner) = (None, None, None);
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match &key[..] {
+                        "name" => name = Some(PathBuf::from(map.next_value::<String>()?)),
+                        "mime" => {
+                            let s = map.next_value::<String>()?;
+                            mime = Some(s.parse().map_err(de::Error::custom)?);
+                        }
+                        "inner" => inner = Some(map.next_value()?),
+                        _ => return Err(de::Error::unknown_field(&key, crate::de::FILE_FIELDS)),
+                    }
+                }
+
+                Ok(File {
+                    name: name.ok_or_else(|| de::Error::custom(Error::NoFilenameError))?,
+                    mime: mime.ok_or_else(|| de::Error::missing_field("mime"))?,
+                    inner: inner.ok_or_else(|| de::Error::missing_field("inner"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct(
+            crate::de::FILE_STRUCT,
+            crate::de::FILE_FIELDS,
+            FileVisitor(PhantomData),
+        )
+    }
+}
+
 impl<T> FromField for File<T>
 where
     T: FromField + 'static,
-    T::Error: std::fmt::Debug,
+    T::Error: std::fmt::Debug + 'static,
 {
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
@@ -60,6 +123,31 @@ where
         }
         .boxed_local()
     }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        let mime = field.content_type().clone();
+        let mut disp = get_content_disposition(&field);
+        let name = disp
+            .remove("filename")
+            .ok_or(Error::NoFilenameError)
+            .map(|s| PathBuf::from(s.to_string()));
+        let inner = T::from_field_deferred(field, config);
+
+        async move {
+            let name = name?;
+            let inner = inner.await.map_err(|e| Error::ActixWebError(e.into()))?;
+
+            Ok(async move {
+                let inner = inner.await.map_err(|e| Error::ActixWebError(e.into()))?;
+                Ok(Self { name, mime, inner })
+            }
+            .boxed_local())
+        }
+        .boxed_local()
+    }
 }
 
 /// Type for wrapping json decoding of multipart field
@@ -102,3 +190,28 @@ impl FromField for String {
         .boxed_local()
     }
 }
+
+macro_rules! from_field_str(
+    { $($ty:ty),* } => {
+        $(
+            impl FromField for $ty {
+                type Error = Error;
+                type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+                fn from_field(field: actix_multipart::Field) -> Self::Future {
+                    async move {
+                        let s = String::from_field(field).await?;
+                        s.parse().map_err(|e: <$ty as std::str::FromStr>::Err| {
+                            Error::ParseError(e.to_string())
+                        })
+                    }
+                    .boxed_local()
+                }
+            }
+        )*
+    }
+);
+
+from_field_str!(u8, u16, u32, u64, u128, usize);
+from_field_str!(i8, i16, i32, i64, i128, isize);
+from_field_str!(f32, f64, bool, char);
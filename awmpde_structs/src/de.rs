@@ -0,0 +1,585 @@
+//! Serde deserialization of multipart bodies, see [`Serde`](crate::Serde).
+//!
+//! Text parts deserialize as strings or scalars parsed from them, repeated names as
+//! sequences. File parts deserialize through [`File`](crate::File), whose inner value can be
+//! `Vec<u8>`, a string or a scalar.
+
+use crate::{get_content_disposition, read_field_limited, Error};
+
+use futures::TryStreamExt;
+use mime::Mime;
+use serde::de::value::SeqDeserializer;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, Unexpected, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use std::collections::HashMap;
+
+/// Struct name by which [`File`](crate::File) asks for the filename and mime of a part.
+pub const FILE_STRUCT: &str = "$awmpde::File";
+/// Fields of [`FILE_STRUCT`].
+pub const FILE_FIELDS: &[&str] = &["name", "mime", "inner"];
+
+type DeError = de::value::Error;
+
+/// Deserializer of a single text value, such as a form field or a `text/plain` part.
+///
+/// Numbers, booleans and chars are parsed from the text, unit enum variants are matched by
+/// name and an empty value deserializes as `None`.
+pub struct ScalarDeserializer<'a>(pub &'a str);
+
+macro_rules! parse_scalar(
+    { $($method:ident => $visit:ident,)* } => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0.trim().parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(self.0), &visitor)),
+                }
+            }
+        )*
+    };
+);
+
+impl<'de, 'a> de::Deserializer<'de> for ScalarDeserializer<'a> {
+    type Error = de::value::Error;
+
+    /// Type isn't known, such as for `flatten` or untagged enums, so `true` and `false` are
+    /// read as booleans, numbers as numbers and anything else as a string.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let s = self.0;
+        if let Ok(v) = s.parse() {
+            visitor.visit_bool(v)
+        } else if let Ok(v) = s.parse() {
+            visitor.visit_u64(v)
+        } else if let Ok(v) = s.parse() {
+            visitor.visit_i64(v)
+        } else if let Some(v) = s.parse::<f64>().ok().filter(|v| v.is_finite()) {
+            visitor.visit_f64(v)
+        } else {
+            visitor.visit_str(s)
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_str(self.0)
+    }
+
+    parse_scalar! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(IntoDeserializer::<'de, Self::Error>::into_deserializer(
+            self.0,
+        ))
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit_struct seq tuple tuple_struct map struct ignored_any
+    }
+}
+
+/// Multipart part buffered for deserialization.
+pub struct Part {
+    pub filename: Option<String>,
+    pub mime: Mime,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    /// Text part, such as a value of url-encoded form.
+    pub fn text(value: String) -> Self {
+        Self {
+            filename: None,
+            mime: mime::TEXT_PLAIN,
+            data: value.into_bytes(),
+        }
+    }
+}
+
+/// Most parts buffered by [`from_multipart`].
+pub const MAX_PARTS: usize = 1000;
+
+/// Buffers all parts of a multipart body and deserializes them into `T`. Parts over `limit`
+/// bytes fail with [`Error::FieldTooLarge`], bodies with more than `total` bytes of parts or
+/// more than [`MAX_PARTS`] parts with [`Error::BodyTooLarge`] and [`Error::TooManyParts`].
+pub async fn from_multipart<T: DeserializeOwned>(
+    mut mp: actix_multipart::Multipart,
+    limit: usize,
+    total: usize,
+) -> Result<T, Error> {
+    let mut parts = Vec::new();
+    let mut size = 0;
+
+    while let Some(field) = mp.try_next().await.map_err(actix_web::Error::from)? {
+        if parts.len() == MAX_PARTS {
+            return Err(Error::TooManyParts(MAX_PARTS));
+        }
+        let mut disp = get_content_disposition(&field);
+        let name = disp
+            .remove("name")
+            .ok_or_else(|| Error::ParseError("part has no name".to_string()))?
+            .into_string();
+        let filename = disp.remove("filename").map(String::from);
+        let mime = field.content_type().clone();
+        let data = read_field_limited(field, limit).await?;
+
+        size += data.len();
+        if size > total {
+            return Err(Error::BodyTooLarge(total));
+        }
+        parts.push((
+            name,
+            Part {
+                filename,
+                mime,
+                data,
+            },
+        ));
+    }
+
+    from_parts(parts)
+}
+
+/// Deserializes `T` from named parts.
+pub fn from_parts<T, I>(parts: I) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    I: IntoIterator<Item = (String, Part)>,
+{
+    Ok(T::deserialize(PartsDeserializer::new(parts))?)
+}
+
+/// Deserializer of a whole multipart body as a map from names to parts.
+pub struct PartsDeserializer {
+    parts: Vec<(String, Vec<Part>)>,
+}
+
+impl PartsDeserializer {
+    pub fn new<I: IntoIterator<Item = (String, Part)>>(parts: I) -> Self {
+        let mut idx: HashMap<String, usize> = HashMap::new();
+        let mut grouped: Vec<(String, Vec<Part>)> = Vec::new();
+
+        for (name, part) in parts {
+            match idx.get(&name) {
+                Some(&i) => grouped[i].1.push(part),
+                None => {
+                    idx.insert(name.clone(), grouped.len());
+                    grouped.push((name, vec![part]));
+                }
+            }
+        }
+
+        Self { parts: grouped }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for PartsDeserializer {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_map(PartsMap {
+            iter: self.parts.into_iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct PartsMap {
+    iter: std::vec::IntoIter<(String, Vec<Part>)>,
+    value: Option<Vec<Part>>,
+}
+
+impl<'de> MapAccess<'de> for PartsMap {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((name, parts)) => {
+                self.value = Some(parts);
+                seed.deserialize(IntoDeserializer::<'de, DeError>::into_deserializer(name))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let parts = self.value.take().expect("value is requested after its key");
+        seed.deserialize(PartValues(parts))
+    }
+}
+
+/// All parts sharing one name. Sequences get every part, other types expect exactly one.
+struct PartValues(Vec<Part>);
+
+impl PartValues {
+    fn single(mut self) -> Result<PartDeserializer, DeError> {
+        if self.0.len() == 1 {
+            Ok(PartDeserializer(self.0.pop().unwrap()))
+        } else {
+            Err(de::Error::custom(format_args!(
+                "expected a single part, found {}",
+                self.0.len()
+            )))
+        }
+    }
+
+    fn seq<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let mut seq = SeqDeserializer::new(self.0.into_iter().map(PartDeserializer));
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+}
+
+macro_rules! forward_single(
+    { $($method:ident)* } => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                self.single()?.$method(visitor)
+            }
+        )*
+    };
+);
+
+impl<'de> de::Deserializer<'de> for PartValues {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.len() == 1 {
+            self.single()?.deserialize_any(visitor)
+        } else {
+            self.seq(visitor)
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.seq(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.seq(visitor)
+    }
+
+    /// Empty part, as sent by browsers for blank inputs, is `None`.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.len() == 1 && self.0[0].data.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_struct(name, fields, visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.single()?.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_single! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_string deserialize_bytes deserialize_byte_buf deserialize_unit
+        deserialize_map deserialize_identifier
+    }
+}
+
+/// Single part. Bytes and `Vec<u8>` get its raw content, other types its text.
+struct PartDeserializer(Part);
+
+impl PartDeserializer {
+    fn text(&self) -> Result<&str, DeError> {
+        std::str::from_utf8(&self.0.data)
+            .map_err(|_| de::Error::invalid_value(Unexpected::Bytes(&self.0.data), &"utf8 text"))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for PartDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! forward_scalar(
+    { $($method:ident)* } => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                ScalarDeserializer(self.text()?).$method(visitor)
+            }
+        )*
+    };
+);
+
+impl<'de> de::Deserializer<'de> for PartDeserializer {
+    type Error = DeError;
+
+    /// Text is guessed as by [`ScalarDeserializer`], anything else is bytes.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match String::from_utf8(self.0.data) {
+            Ok(s) => ScalarDeserializer(&s).deserialize_any(visitor),
+            Err(e) => visitor.visit_byte_buf(e.into_bytes()),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match String::from_utf8(self.0.data) {
+            Ok(s) => visitor.visit_string(s),
+            Err(e) => Err(de::Error::invalid_value(
+                Unexpected::Bytes(e.as_bytes()),
+                &"utf8 text",
+            )),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_byte_buf(self.0.data)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_byte_buf(self.0.data)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let mut seq = SeqDeserializer::<_, DeError>::new(self.0.data.into_iter());
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if name == FILE_STRUCT {
+            let Part {
+                filename,
+                mime,
+                data,
+            } = self.0;
+            let inner = Part {
+                filename: None,
+                mime: mime.clone(),
+                data,
+            };
+            visitor.visit_map(FileMap {
+                filename,
+                mime: Some(mime.to_string()),
+                inner: Some(inner),
+            })
+        } else {
+            ScalarDeserializer(self.text()?).deserialize_struct(name, fields, visitor)
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        ScalarDeserializer(self.text()?).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    forward_scalar! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_i128 deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_u128 deserialize_f32 deserialize_f64 deserialize_char deserialize_str
+        deserialize_unit deserialize_map deserialize_identifier
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// Fields of [`FILE_STRUCT`], `name` is left out for parts without filename.
+struct FileMap {
+    filename: Option<String>,
+    mime: Option<String>,
+    inner: Option<Part>,
+}
+
+impl<'de> MapAccess<'de> for FileMap {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let key = if self.filename.is_some() {
+            FILE_FIELDS[0]
+        } else if self.mime.is_some() {
+            FILE_FIELDS[1]
+        } else if self.inner.is_some() {
+            FILE_FIELDS[2]
+        } else {
+            return Ok(None);
+        };
+        seed.deserialize(IntoDeserializer::<'de, DeError>::into_deserializer(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        if let Some(filename) = self.filename.take() {
+            seed.deserialize(IntoDeserializer::<'de, DeError>::into_deserializer(
+                filename,
+            ))
+        } else if let Some(mime) = self.mime.take() {
+            seed.deserialize(IntoDeserializer::<'de, DeError>::into_deserializer(mime))
+        } else {
+            let inner = self.inner.take().expect("value is requested after its key");
+            seed.deserialize(PartDeserializer(inner))
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::*;
+
+pub use exif::{Field, In, Tag, Value};
+
+use exif::{Context, Reader};
+use futures::TryFutureExt;
+
+/// Position of the camera from GPS tags, in degrees and meters above sea level.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub altitude: Option<f64>,
+}
+
+/// EXIF metadata of an uploaded photo. Parts without EXIF give empty metadata.
+///
+/// With `strip_gps` set in [`ImageConfig`](crate::images::ImageConfig) GPS tags are dropped.
+#[derive(Clone, Debug, Default)]
+pub struct Exif {
+    /// EXIF orientation, from 1 to 8
+    pub orientation: Option<u16>,
+    /// Capture time as `YYYY:MM:DD HH:MM:SS`
+    pub captured_at: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub gps: Option<GpsPosition>,
+    /// All parsed tags
+    pub fields: Vec<Field>,
+}
+
+impl Exif {
+    /// Parses EXIF of a JPEG, PNG, TIFF, WebP or HEIF image.
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, Error> {
+        let exif = match Reader::new().read_from_container(&mut Cursor::new(buf)) {
+            Ok(exif) => exif,
+            Err(exif::Error::NotFound(_)) => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let field = |tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+
+        Ok(Self {
+            orientation: field(Tag::Orientation)
+                .and_then(|v| v.get_uint(0))
+                .map(|o| o as u16),
+            captured_at: field(Tag::DateTimeOriginal)
+                .or_else(|| field(Tag::DateTime))
+                .and_then(ascii),
+            make: field(Tag::Make).and_then(ascii),
+            model: field(Tag::Model).and_then(ascii),
+            gps: gps_position(&exif),
+            fields: exif.fields().cloned().collect(),
+        })
+    }
+
+    /// Drops GPS tags.
+    pub fn strip_gps(&mut self) {
+        self.gps = None;
+        self.fields.retain(|f| f.tag.context() != Context::Gps);
+    }
+}
+
+fn ascii(value: &Value) -> Option<String> {
+    match value {
+        Value::Ascii(strings) => strings
+            .first()
+            .map(|s| String::from_utf8_lossy(s).trim().to_string())
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
+
+fn gps_position(exif: &exif::Exif) -> Option<GpsPosition> {
+    let field = |tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+    // Degrees, minutes and seconds, negative for south and west
+    let degrees = |tag, reference, negative: &[u8]| match field(tag)? {
+        Value::Rational(dms) if dms.len() == 3 => {
+            let deg = dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0;
+            match field(reference) {
+                Some(Value::Ascii(r)) if r.first().map(|r| &r[..]) == Some(negative) => Some(-deg),
+                _ => Some(deg),
+            }
+        }
+        _ => None,
+    };
+    let altitude = match field(Tag::GPSAltitude) {
+        Some(Value::Rational(alt)) if !alt.is_empty() => match field(Tag::GPSAltitudeRef) {
+            Some(Value::Byte(r)) if r.first() == Some(&1) => Some(-alt[0].to_f64()),
+            _ => Some(alt[0].to_f64()),
+        },
+        _ => None,
+    };
+
+    Some(GpsPosition {
+        latitude: degrees(Tag::GPSLatitude, Tag::GPSLatitudeRef, b"S")?,
+        longitude: degrees(Tag::GPSLongitude, Tag::GPSLongitudeRef, b"W")?,
+        altitude,
+    })
+}
+
+impl FromField for Exif {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        let strip_gps = config.image.strip_gps;
+        async move {
+            let vec = Vec::<u8>::from_field(field).await.unwrap();
+            let mut exif = Self::from_bytes(&vec)?;
+            if strip_gps {
+                exif.strip_gps();
+            }
+            Ok(futures::future::ok(exif).boxed_local())
+        }
+        .boxed_local()
+    }
+}
+
+/// Orientation from EXIF of an image, 1 if there is none.
+pub(crate) fn orientation(buf: &[u8]) -> u32 {
+    Reader::new()
+        .read_from_container(&mut Cursor::new(buf))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(Tag::Orientation, In::PRIMARY)?
+                .value
+                .get_uint(0)
+        })
+        .unwrap_or(1)
+}
+
+/// Blanks the GPS tags of a JPEG in place, leaving an empty GPS IFD behind. The EXIF segment
+/// has to be in `buf`, other images are left as is.
+pub(crate) fn strip_gps(buf: &mut [u8]) {
+    if !buf.starts_with(&[0xff, 0xd8]) {
+        return;
+    }
+
+    let mut pos = 2;
+    while let Some(&[0xff, marker]) = buf.get(pos..pos + 2) {
+        match marker {
+            // Markers without payload
+            0x01 | 0xd0..=0xd8 | 0xff => {
+                pos += if marker == 0xff { 1 } else { 2 };
+                continue;
+            }
+            // Image data starts, metadata is over
+            0xda | 0xd9 => return,
+            _ => {}
+        }
+
+        let len = match buf.get(pos + 2..pos + 4) {
+            Some(len) => usize::from(u16::from_be_bytes([len[0], len[1]])),
+            None => return,
+        };
+        let end = (pos + 2 + len).min(buf.len());
+        let segment = match buf.get_mut(pos + 4..end) {
+            Some(segment) => segment,
+            None => return,
+        };
+        if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+            strip_tiff_gps(&mut segment[6..]);
+        }
+        pos += 2 + len;
+    }
+}
+
+/// Blanks the GPS IFD of TIFF structure in `tiff`.
+fn strip_tiff_gps(tiff: &mut [u8]) -> Option<()> {
+    let big_endian = match tiff.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let u16_at = |tiff: &[u8], at: usize| {
+        let b = tiff.get(at..at + 2)?;
+        let b = [b[0], b[1]];
+        Some(usize::from(if big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        }))
+    };
+    let u32_at = |tiff: &[u8], at: usize| {
+        let b = tiff.get(at..at + 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(if big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        } as usize)
+    };
+
+    let ifd0 = u32_at(tiff, 4)?;
+    let gps = (0..u16_at(tiff, ifd0)?)
+        .map(|i| ifd0 + 2 + 12 * i)
+        .find(|&entry| u16_at(tiff, entry) == Some(0x8825))
+        .and_then(|entry| u32_at(tiff, entry + 8))?;
+
+    let count = u16_at(tiff, gps)?;
+    for entry in (0..count).map(|i| gps + 2 + 12 * i) {
+        let size: usize = match u16_at(tiff, entry + 2)? {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 | 11 => 4,
+            5 | 10 | 12 => 8,
+            _ => 0,
+        };
+        let size = u32_at(tiff, entry + 4)?.saturating_mul(size);
+        // Values over 4 bytes are stored out of the entry
+        if size > 4 {
+            let offset = u32_at(tiff, entry + 8)?;
+            if let Some(value) = tiff.get_mut(offset..offset.saturating_add(size)) {
+                value.iter_mut().for_each(|b| *b = 0);
+            }
+        }
+        tiff.get_mut(entry..entry + 12)?
+            .iter_mut()
+            .for_each(|b| *b = 0);
+    }
+    // No entries, and the blanked first entry reads as no next IFD
+    tiff.get_mut(gps..gps + 2)?.iter_mut().for_each(|b| *b = 0);
+    Some(())
+}
@@ -0,0 +1,161 @@
+use super::*;
+
+use actix_web::http::header::CONTENT_TYPE;
+
+#[cfg(any(
+    feature = "toml",
+    feature = "yaml",
+    feature = "xml",
+    feature = "msgpack",
+    feature = "cbor"
+))]
+macro_rules! ff_format(
+    { $(#[$meta:meta])* $ty:ident, $decode:path } => {
+        $(#[$meta])*
+        #[derive(Deref, DerefMut)]
+        pub struct $ty<T>(pub T);
+
+        impl<T: DeserializeOwned + 'static> FromField for $ty<T> {
+            type Error = Error;
+            type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+            fn from_field(field: actix_multipart::Field) -> Self::Future {
+                let vec = Vec::<u8>::from_field(field);
+                async move {
+                    let vec = vec.await.unwrap();
+                    Ok(Self($decode(&vec[..])?))
+                }
+                .boxed_local()
+            }
+        }
+    };
+);
+
+#[cfg(feature = "toml")]
+ff_format!(
+    /// Type for wrapping toml decoding of multipart field
+    Toml,
+    toml::from_slice
+);
+
+#[cfg(feature = "yaml")]
+ff_format!(
+    /// Type for wrapping yaml decoding of multipart field
+    Yaml,
+    serde_yaml::from_slice
+);
+
+#[cfg(feature = "xml")]
+ff_format!(
+    /// Type for wrapping xml decoding of multipart field
+    Xml,
+    quick_xml::de::from_reader
+);
+
+#[cfg(feature = "msgpack")]
+ff_format!(
+    /// Type for wrapping MessagePack decoding of multipart field
+    MsgPack,
+    rmp_serde::from_slice
+);
+
+#[cfg(feature = "cbor")]
+ff_format!(
+    /// Type for wrapping CBOR decoding of multipart field
+    Cbor,
+    serde_cbor::from_slice
+);
+
+/// Type for decoding multipart field according to its own `Content-Type`.
+///
+/// A part without `Content-Type` is treated as `text/plain`, whose text is deserialized as a
+/// single scalar value. Other formats than json and url-encoded need their crate features.
+/// Unknown types fail with `415 Unsupported Media Type`.
+#[derive(Deref, DerefMut)]
+pub struct Auto<T>(pub T);
+
+impl<T: DeserializeOwned + 'static> FromField for Auto<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        let mime = if field.headers().contains_key(CONTENT_TYPE) {
+            field.content_type().clone()
+        } else {
+            mime::TEXT_PLAIN
+        };
+        let vec = Vec::<u8>::from_field(field);
+        async move {
+            let vec = vec.await.unwrap();
+            Ok(Self(decode_auto(&mime, vec)?))
+        }
+        .boxed_local()
+    }
+}
+
+fn decode_auto<T: DeserializeOwned>(mime: &Mime, vec: Vec<u8>) -> Result<T, Error> {
+    let tp = (
+        mime.type_().as_str(),
+        mime.subtype().as_str(),
+        mime.suffix().map(|s| s.as_str()),
+    );
+
+    match tp {
+        ("application", "json", _) | ("application", _, Some("json")) => {
+            Ok(serde_json::from_slice(&vec)?)
+        }
+        ("application", "x-www-form-urlencoded", _) => {
+            serde_urlencoded::from_bytes(&vec).map_err(|e| Error::ParseError(e.to_string()))
+        }
+        ("text", "plain", _) => {
+            let s = String::from_utf8(vec)?;
+            T::deserialize(de::ScalarDeserializer(&s)).map_err(|e| Error::ParseError(e.to_string()))
+        }
+        #[cfg(feature = "toml")]
+        ("application", "toml", _) => Ok(toml::from_slice(&vec)?),
+        #[cfg(feature = "yaml")]
+        ("application", "yaml", _)
+        | ("application", "x-yaml", _)
+        | ("text", "yaml", _)
+        | ("text", "x-yaml", _) => Ok(serde_yaml::from_slice(&vec)?),
+        #[cfg(feature = "xml")]
+        ("application", "xml", _) | ("text", "xml", _) | (_, _, Some("xml")) => {
+            Ok(quick_xml::de::from_reader(&vec[..])?)
+        }
+        #[cfg(feature = "msgpack")]
+        ("application", "msgpack", _)
+        | ("application", "x-msgpack", _)
+        | ("application", "vnd.msgpack", _) => Ok(rmp_serde::from_slice(&vec)?),
+        #[cfg(feature = "cbor")]
+        ("application", "cbor", _) => Ok(serde_cbor::from_slice(&vec)?),
+        _ => Err(Error::UnsupportedMediaType(mime.clone())),
+    }
+}
+
+/// Type for wrapping protobuf decoding of multipart field
+///
+/// The part must have `application/x-protobuf` or `application/protobuf` content type and be
+/// at most `LIMIT` bytes long.
+#[cfg(feature = "protobuf")]
+#[derive(Deref, DerefMut)]
+pub struct Protobuf<T, const LIMIT: usize = { 1 << 20 }>(pub T);
+
+#[cfg(feature = "protobuf")]
+impl<T: prost::Message + Default + 'static, const LIMIT: usize> FromField for Protobuf<T, LIMIT> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        async move {
+            let mime = field.content_type();
+            match (mime.type_().as_str(), mime.subtype().as_str()) {
+                ("application", "x-protobuf") | ("application", "protobuf") => {}
+                _ => return Err(Error::UnsupportedMediaType(mime.clone())),
+            }
+
+            let vec = read_field_limited(field, LIMIT).await?;
+            Ok(Self(T::decode(&vec[..])?))
+        }
+        .boxed_local()
+    }
+}
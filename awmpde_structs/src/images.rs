@@ -1,11 +1,1014 @@
 use super::*;
 
+pub use image::imageops::FilterType;
+pub use image::{Bgr, Bgra, ColorType, ImageFormat, Luma, LumaA, Rgb, Rgba};
+
+use actix_web::web::{self, Bytes};
+use futures::TryFutureExt;
 use image::io::Reader as ImgReader;
-use image::{Bgr, Bgra, ImageFormat};
+use image::ImageDecoder as _;
+use once_cell::sync::Lazy;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+mod decoder;
+pub use decoder::*;
+mod frames;
+pub use frames::*;
+#[cfg(feature = "ndarray")]
+mod tensor;
+#[cfg(feature = "ndarray")]
+pub use tensor::*;
+
+/// Limits of image size, checked from the image header before decoding.
+///
+/// The full size is checked, also for [`Resized`] and [`Thumbnail`] JPEGs decoded at down to
+/// 1/8 of it, so limits don't depend on the requested size.
+#[derive(Clone, Copy, Debug)]
+pub struct ImageLimits {
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub max_pixels: Option<u64>,
+    /// Maximum size of decoded image in bytes, of all frames for [`Frames`]
+    pub max_alloc: Option<u64>,
+    /// Maximum number of [`Frames`]
+    pub max_frames: Option<usize>,
+    /// Maximum pixels of all [`Frames`] together
+    pub max_total_pixels: Option<u64>,
+}
+
+impl Default for ImageLimits {
+    fn default() -> Self {
+        Self {
+            max_width: None,
+            max_height: None,
+            max_pixels: None,
+            max_alloc: Some(512 * 1024 * 1024),
+            max_frames: None,
+            max_total_pixels: None,
+        }
+    }
+}
+
+impl ImageLimits {
+    /// No limits at all.
+    pub fn none() -> Self {
+        Self {
+            max_alloc: None,
+            ..Self::default()
+        }
+    }
+
+    fn check(&self, width: u32, height: u32, bytes_per_pixel: u64) -> Result<(), DecodeError> {
+        let pixels = u64::from(width) * u64::from(height);
+        let over = self.max_width.map_or(false, |max| width > max)
+            || self.max_height.map_or(false, |max| height > max)
+            || self.max_pixels.map_or(false, |max| pixels > max)
+            || self
+                .max_alloc
+                .map_or(false, |max| pixels.saturating_mul(bytes_per_pixel) > max);
+
+        if over {
+            Err(DecodeError::TooLarge(width, height))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks `frames` RGBA frames of `pixels` in total.
+    fn check_frames(&self, frames: usize, pixels: u64) -> Result<(), DecodeError> {
+        let over = self.max_frames.map_or(false, |max| frames > max)
+            || self.max_total_pixels.map_or(false, |max| pixels > max)
+            || self
+                .max_alloc
+                .map_or(false, |max| pixels.saturating_mul(4) > max);
+
+        if over {
+            Err(DecodeError::AnimationTooLarge)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Permits of the default [`ImageConfig`], one per CPU.
+static DEFAULT_PERMITS: Lazy<Arc<Semaphore>> = Lazy::new(|| {
+    let cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+    Arc::new(Semaphore::new(cpus))
+});
+
+/// Settings of image decoding, part of [`FieldConfig`].
+///
+/// By default images are decoded on actix's blocking thread pool, so several image fields
+/// of a request are decoded in parallel, and are limited to 512 MiB once decoded. At most one
+/// image per CPU is decoded at once, shared by all requests using the default.
+#[derive(Clone)]
+pub struct ImageConfig {
+    blocking: bool,
+    permits: Arc<Semaphore>,
+    limits: ImageLimits,
+    formats: Option<Vec<ImageFormat>>,
+    still: bool,
+    resize: Option<Resize>,
+    decoder: Arc<dyn ImageDecoder>,
+    #[cfg(feature = "ndarray")]
+    tensor: TensorConfig,
+    #[cfg(feature = "exif")]
+    auto_orient: bool,
+    #[cfg(feature = "exif")]
+    pub(crate) strip_gps: bool,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            blocking: true,
+            permits: DEFAULT_PERMITS.clone(),
+            limits: ImageLimits::default(),
+            formats: None,
+            still: false,
+            resize: None,
+            decoder: decoder::default_decoder(),
+            #[cfg(feature = "ndarray")]
+            tensor: TensorConfig::default(),
+            #[cfg(feature = "exif")]
+            auto_orient: false,
+            #[cfg(feature = "exif")]
+            strip_gps: false,
+        }
+    }
+}
+
+impl ImageConfig {
+    /// Decodes images right on the async executor.
+    pub fn inline(mut self) -> Self {
+        self.blocking = false;
+        self
+    }
+
+    /// Decodes at most `n` images at once, shared by all clones of this config. Panics if `n`
+    /// is zero, with which no image would ever be decoded.
+    pub fn max_concurrent(mut self, n: usize) -> Self {
+        assert!(n > 0, "max concurrent decodes can't be zero");
+        self.permits = Arc::new(Semaphore::new(n));
+        self
+    }
+
+    /// Replaces all limits of image size.
+    pub fn limits(mut self, limits: ImageLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn max_width(mut self, width: u32) -> Self {
+        self.limits.max_width = Some(width);
+        self
+    }
+
+    pub fn max_height(mut self, height: u32) -> Self {
+        self.limits.max_height = Some(height);
+        self
+    }
+
+    pub fn max_pixels(mut self, pixels: u64) -> Self {
+        self.limits.max_pixels = Some(pixels);
+        self
+    }
+
+    /// Limits size of decoded image in bytes.
+    pub fn max_alloc(mut self, bytes: u64) -> Self {
+        self.limits.max_alloc = Some(bytes);
+        self
+    }
+
+    pub fn max_frames(mut self, frames: usize) -> Self {
+        self.limits.max_frames = Some(frames);
+        self
+    }
+
+    pub fn max_total_pixels(mut self, pixels: u64) -> Self {
+        self.limits.max_total_pixels = Some(pixels);
+        self
+    }
+
+    /// Fails on animated images, except for [`Frames`].
+    pub fn reject_animated(mut self) -> Self {
+        self.still = true;
+        self
+    }
+
+    /// Accepts only images of `formats`, detected from the image itself.
+    pub fn allowed_formats(mut self, formats: &[ImageFormat]) -> Self {
+        self.formats = Some(formats.to_vec());
+        self
+    }
+
+    /// Size of [`Resized`] and [`Thumbnail`] images.
+    pub fn resize(mut self, resize: Resize) -> Self {
+        self.resize = Some(resize);
+        self
+    }
+
+    /// Decodes images with `decoder` instead of the default one.
+    pub fn decoder(mut self, decoder: impl ImageDecoder + 'static) -> Self {
+        self.decoder = Arc::new(decoder);
+        self
+    }
+
+    /// Shape of [`Tensor`] images.
+    #[cfg(feature = "ndarray")]
+    pub fn tensor(mut self, tensor: TensorConfig) -> Self {
+        self.tensor = tensor;
+        self
+    }
+
+    /// Rotates and flips decoded images upright according to their EXIF orientation.
+    #[cfg(feature = "exif")]
+    pub fn auto_orient(mut self) -> Self {
+        self.auto_orient = true;
+        self
+    }
+
+    /// Blanks GPS tags of JPEG images kept by [`Probed`] and drops them from
+    /// [`Exif`](crate::exif_field::Exif).
+    ///
+    /// Only JPEG bytes are changed, EXIF of PNG, WebP, TIFF or HEIF images kept by [`Probed`]
+    /// still has GPS tags. JPEG EXIF is always before the frame header, so it's in the bytes
+    /// read to probe the image.
+    #[cfg(feature = "exif")]
+    pub fn strip_gps(mut self) -> Self {
+        self.strip_gps = true;
+        self
+    }
+
+    #[cfg(feature = "exif")]
+    fn orient(&self, buf: &[u8], img: image::DynamicImage) -> image::DynamicImage {
+        if !self.auto_orient {
+            return img;
+        }
+
+        match crate::exif_field::orientation(buf) {
+            2 => img.fliph(),
+            3 => img.rotate180(),
+            4 => img.flipv(),
+            5 => img.rotate90().fliph(),
+            6 => img.rotate90(),
+            7 => img.rotate270().fliph(),
+            8 => img.rotate270(),
+            _ => img,
+        }
+    }
+
+    #[cfg(not(feature = "exif"))]
+    fn orient(&self, _buf: &[u8], img: image::DynamicImage) -> image::DynamicImage {
+        img
+    }
+
+    /// Decodes an image of `color`, at least as large as needed for `resize`.
+    fn decode(
+        &self,
+        buf: &[u8],
+        ct: &Mime,
+        color: Option<ColorType>,
+        resize: Option<&Resize>,
+    ) -> Result<image::DynamicImage, DecodeError> {
+        let format = self.detect_format(buf, ct)?;
+        let (w, h) = check_limits(buf, format, &self.limits)?;
+        if self.still && is_animated(format, buf) {
+            return Err(DecodeError::Animated);
+        }
+        let hint = DecodeHint {
+            color,
+            scale: resize.map_or(8, |resize| resize.dct_scale(w, h)),
+        };
+
+        let img = if self.decoder.decodes(format) {
+            self.decoder.decode(buf, format, hint)?
+        } else {
+            ImageRs.decode(buf, format, hint)?
+        };
+        Ok(self.orient(buf, img))
+    }
+
+    /// Changes raw image bytes before they're stored.
+    #[cfg(feature = "exif")]
+    fn before_store(&self, head: &mut [u8]) {
+        if self.strip_gps {
+            crate::exif_field::strip_gps(head);
+        }
+    }
+
+    #[cfg(not(feature = "exif"))]
+    fn before_store(&self, _head: &mut [u8]) {}
+
+    /// Format of the image from its magic bytes, or from the content type for formats without
+    /// them. Fails if they disagree or the format isn't allowed.
+    fn detect_format(&self, buf: &[u8], ct: &Mime) -> Result<ImageFormat, DecodeError> {
+        use image::error::{ImageFormatHint, UnsupportedError};
+
+        let declared = mime_format(ct);
+        let format = match (declared, sniff_format(buf)) {
+            (Some(declared), Some(actual)) if declared != actual => {
+                return Err(DecodeError::FormatMismatch(ct.clone(), actual))
+            }
+            (_, Some(format)) | (Some(format), None) => format,
+            (None, None) => {
+                let hint = ImageFormatHint::Unknown;
+                return Err(image::ImageError::Unsupported(UnsupportedError::from(hint)).into());
+            }
+        };
+
+        match &self.formats {
+            Some(formats) if !formats.contains(&format) => Err(DecodeError::NotAllowed(format)),
+            _ => Ok(format),
+        }
+    }
+
+    /// Starts `decode`, right away unless all permits are taken.
+    fn spawn<T, F>(&self, decode: F) -> Deferred<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T, DecodeError> + Send + 'static,
+    {
+        if !self.blocking {
+            return futures::future::ready(decode().map_err(Error::from)).boxed_local();
+        }
+
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => block(permit, decode).boxed_local(),
+            Err(_) => {
+                let permits = self.permits.clone();
+                let job = actix_web::rt::spawn(async move {
+                    let permit = permits
+                        .acquire_owned()
+                        .await
+                        .expect("semaphore is never closed");
+                    block(permit, decode).await
+                });
+                async move {
+                    job.await.map_err(|e| {
+                        Error::ActixWebError(actix_web::error::ErrorInternalServerError(e))
+                    })?
+                }
+                .boxed_local()
+            }
+        }
+    }
+}
+
+fn block<T, F>(permit: OwnedSemaphorePermit, decode: F) -> impl Future<Output = Result<T, Error>>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, DecodeError> + Send + 'static,
+{
+    let job = web::block(move || {
+        let _permit = permit;
+        decode()
+    });
+    async move { Ok(job.await.map_err(|e| Error::ActixWebError(e.into()))??) }
+}
+
+/// Error of decoding, unlike [`Error`] it can be sent from the thread pool.
+pub(crate) enum DecodeError {
+    Image(image::ImageError),
+    TooLarge(u32, u32),
+    FormatMismatch(Mime, ImageFormat),
+    NotAllowed(ImageFormat),
+    Animated,
+    AnimationTooLarge,
+}
+
+impl From<image::ImageError> for DecodeError {
+    fn from(e: image::ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::Image(e) => Error::ImageDecodeError(e),
+            DecodeError::TooLarge(w, h) => Error::ImageTooLarge(w, h),
+            DecodeError::FormatMismatch(ct, format) => Error::ImageFormatMismatch(ct, format),
+            DecodeError::NotAllowed(format) => Error::ImageFormatNotAllowed(format),
+            DecodeError::Animated => Error::ImageAnimated,
+            DecodeError::AnimationTooLarge => Error::AnimationTooLarge,
+        }
+    }
+}
+
+/// Reads the field and starts decoding it with `decode` according to `config`.
+fn read_and_decode<T: Send + 'static>(
+    field: actix_multipart::Field,
+    config: &ImageConfig,
+    decode: fn(Vec<u8>, Mime, &ImageConfig) -> Result<T, DecodeError>,
+) -> DeferredRead<T> {
+    let config = config.clone();
+    async move {
+        let ct = field.content_type().clone();
+        let vec = Vec::<u8>::from_field(field).await.unwrap();
+        let job = config.clone();
+        Ok(config.spawn(move || decode(vec, ct, &job)))
+    }
+    .boxed_local()
+}
+
+/// Image format of a content type.
+///
+/// AVIF is decoded only with `avif-decoder` feature of `image` enabled. QOI isn't supported
+/// by `image` 0.23.
+fn mime_format(ct: &Mime) -> Option<ImageFormat> {
+    if ct.type_() != mime::IMAGE {
+        return None;
+    }
+
+    Some(match ct.subtype().as_str() {
+        "jpeg" | "pjpeg" => ImageFormat::Jpeg,
+        "png" | "apng" => ImageFormat::Png,
+        "gif" => ImageFormat::Gif,
+        "bmp" | "x-bmp" | "x-ms-bmp" => ImageFormat::Bmp,
+        "webp" => ImageFormat::WebP,
+        "tiff" => ImageFormat::Tiff,
+        "x-icon" | "vnd.microsoft.icon" => ImageFormat::Ico,
+        "x-tga" | "x-targa" => ImageFormat::Tga,
+        "x-portable-anymap" | "x-portable-bitmap" | "x-portable-graymap" | "x-portable-pixmap" => {
+            ImageFormat::Pnm
+        }
+        "avif" => ImageFormat::Avif,
+        _ => return None,
+    })
+}
+
+/// Image format from magic bytes.
+fn sniff_format(buf: &[u8]) -> Option<ImageFormat> {
+    // `image` only knows one size of the `ftyp` box
+    match buf.get(4..12) {
+        Some(b"ftypavif") | Some(b"ftypavis") => Some(ImageFormat::Avif),
+        _ => image::guess_format(buf).ok(),
+    }
+}
+
+/// Reads dimensions and color type, if known for the format, from the image header.
+fn read_header(
+    cur: Cursor<&[u8]>,
+    format: ImageFormat,
+) -> image::ImageResult<(u32, u32, Option<ColorType>)> {
+    use image::codecs::*;
+
+    macro_rules! header(
+        { $decoder:expr } => {{
+            let decoder = $decoder?;
+            let (w, h) = decoder.dimensions();
+            (w, h, Some(decoder.color_type()))
+        }};
+    );
+
+    Ok(match format {
+        ImageFormat::Png => header!(png::PngDecoder::new(cur)),
+        ImageFormat::Jpeg => header!(jpeg::JpegDecoder::new(cur)),
+        ImageFormat::Gif => header!(gif::GifDecoder::new(cur)),
+        ImageFormat::WebP => header!(webp::WebPDecoder::new(cur)),
+        ImageFormat::Tiff => header!(tiff::TiffDecoder::new(cur)),
+        ImageFormat::Tga => header!(tga::TgaDecoder::new(cur)),
+        ImageFormat::Bmp => header!(bmp::BmpDecoder::new(cur)),
+        ImageFormat::Ico => header!(ico::IcoDecoder::new(cur)),
+        ImageFormat::Pnm => header!(pnm::PnmDecoder::new(cur)),
+        ImageFormat::Farbfeld => header!(farbfeld::FarbfeldDecoder::new(cur)),
+        format => {
+            let (w, h) = ImgReader::with_format(cur, format).into_dimensions()?;
+            (w, h, None)
+        }
+    })
+}
+
+/// Dimensions of the image, if within `limits`.
+fn check_limits(
+    buf: &[u8],
+    format: ImageFormat,
+    limits: &ImageLimits,
+) -> Result<(u32, u32), DecodeError> {
+    let (w, h, color) = read_header(Cursor::new(buf), format)?;
+    // Assume the widest color type if unknown
+    let color = color.unwrap_or(ColorType::Rgba16);
+    limits.check(w, h, u64::from(color.bytes_per_pixel()))?;
+    Ok((w, h))
+}
+
+/// Image header, read without decoding the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Color type of decoded image, `None` if not known without decoding
+    pub color_type: Option<ColorType>,
+    /// Animated GIF, PNG or WebP. A GIF without a loop extension whose first frame is over
+    /// 1 MiB is taken as still
+    pub animated: bool,
+}
+
+/// Bytes of a field kept to find the image header in.
+const HEADER_LIMIT: usize = 1 << 20;
+
+impl ImageInfo {
+    /// Parses header from the beginning of an image.
+    pub fn probe(buf: &[u8], ct: &Mime) -> Result<Self, Error> {
+        Ok(Self::probe_with(buf, ct, &ImageConfig::default())?)
+    }
+
+    fn probe_with(buf: &[u8], ct: &Mime, config: &ImageConfig) -> Result<Self, DecodeError> {
+        let format = config.detect_format(buf, ct)?;
+        let (width, height, color_type) = read_header(Cursor::new(buf), format)?;
+        let bytes_per_pixel = color_type.unwrap_or(ColorType::Rgba16).bytes_per_pixel();
+        config
+            .limits
+            .check(width, height, u64::from(bytes_per_pixel))?;
+        let animated = is_animated(format, buf);
+        if config.still && animated {
+            return Err(DecodeError::Animated);
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            color_type,
+            animated,
+        })
+    }
+
+    /// Reads the beginning of the field until the header and the chunks telling if the image
+    /// is animated are complete, then parses it once. Returns the info and the bytes read.
+    async fn read_head(
+        field: &mut actix_multipart::Field,
+        config: &ImageConfig,
+    ) -> Result<(Self, Vec<u8>), Error> {
+        let ct = field.content_type().clone();
+        let mut head = Vec::new();
+
+        loop {
+            let end = match field.next().await {
+                Some(chunk) => {
+                    head.extend_from_slice(&chunk.map_err(actix_web::Error::from)?);
+                    false
+                }
+                None => true,
+            };
+            let last = end || head.len() >= HEADER_LIMIT;
+            if !last && !head_complete(&head, &ct, config) {
+                continue;
+            }
+
+            match Self::probe_with(&head, &ct, config) {
+                Ok(info) => return Ok((info, head)),
+                Err(e) if last => {
+                    if !end {
+                        drain(field).await?;
+                    }
+                    return Err(e.into());
+                }
+                // Header spans more segments than expected
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Reads the rest of `field`.
+async fn drain(field: &mut actix_multipart::Field) -> Result<(), Error> {
+    while let Some(chunk) = field.next().await {
+        chunk.map_err(actix_web::Error::from)?;
+    }
+    Ok(())
+}
+
+/// Whether `buf` is long enough to parse the image header from it.
+fn head_complete(buf: &[u8], ct: &Mime, config: &ImageConfig) -> bool {
+    let format = match config.detect_format(buf, ct) {
+        Ok(format) => format,
+        // Format may still be sniffed from more bytes
+        Err(DecodeError::Image(_)) => return false,
+        Err(_) => return true,
+    };
+
+    match format {
+        ImageFormat::Jpeg => {
+            let mut pos = 2;
+            loop {
+                let (marker, len) = match buf.get(pos..pos + 4) {
+                    Some(&[0xff, marker, hi, lo]) => (marker, u16::from_be_bytes([hi, lo])),
+                    Some(_) => return true,
+                    None => return false,
+                };
+                // Frame header, any SOFn but DHT, JPG and DAC
+                if marker & 0xf0 == 0xc0 && ![0xc4, 0xc8, 0xcc].contains(&marker) {
+                    return buf.len() >= pos + 2 + usize::from(len);
+                }
+                pos += 2 + usize::from(len);
+            }
+        }
+        // Dimensions of all three kinds of WebP are within the first 30 bytes
+        ImageFormat::WebP => buf.len() >= 30,
+        format => animation(format, buf).is_some(),
+    }
+}
+
+/// Looks for animation chunks before the first frame, or a second GIF image, `None` if `buf`
+/// ends before.
+fn animation(format: ImageFormat, buf: &[u8]) -> Option<bool> {
+    match format {
+        ImageFormat::Gif => {
+            // Skip the global color table
+            let flags = *buf.get(10)?;
+            let mut pos = 13;
+            if flags & 0x80 != 0 {
+                pos += 3 << ((flags & 0x07) + 1);
+            }
+            // Animated if it has a loop extension or a second image, whichever comes first
+            let mut images = 0;
+            loop {
+                match *buf.get(pos)? {
+                    0x21 => {
+                        let label = *buf.get(pos + 1)?;
+                        pos += 2;
+                        if label == 0xff && buf.get(pos..pos + 12)? == b"\x0bNETSCAPE2.0" {
+                            return Some(true);
+                        }
+                    }
+                    0x2c if images > 0 => return Some(true),
+                    0x2c => {
+                        images += 1;
+                        // Descriptor, local color table and LZW code size
+                        let flags = *buf.get(pos + 9)?;
+                        pos += 10;
+                        if flags & 0x80 != 0 {
+                            pos += 3 << ((flags & 0x07) + 1);
+                        }
+                        pos += 1;
+                    }
+                    // Trailer or garbage
+                    _ => return Some(false),
+                }
+                loop {
+                    let len = usize::from(*buf.get(pos)?);
+                    pos += 1 + len;
+                    if len == 0 {
+                        break;
+                    }
+                }
+            }
+        }
+        ImageFormat::Png => {
+            let mut chunks = buf.get(8..)?;
+            loop {
+                let len = chunks
+                    .get(..4)
+                    .map(|l| u32::from_be_bytes([l[0], l[1], l[2], l[3]]))?;
+                match chunks.get(4..8)? {
+                    b"acTL" => return Some(true),
+                    b"IDAT" => return Some(false),
+                    _ => {}
+                }
+                // Length, type and crc
+                let next = 12 + len as usize;
+                chunks = chunks.get(next..)?;
+            }
+        }
+        // Animation flag of the extended header
+        ImageFormat::WebP => {
+            let flags = *buf.get(20)?;
+            Some(buf.get(12..16) == Some(b"VP8X") && flags & 0x02 != 0)
+        }
+        _ => Some(false),
+    }
+}
+
+fn is_animated(format: ImageFormat, buf: &[u8]) -> bool {
+    animation(format, buf).unwrap_or(false)
+}
+
+impl FromField for ImageInfo {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        let config = config.image.clone();
+        async move {
+            let mut field = field;
+            let (info, _) = Self::read_head(&mut field, &config).await?;
+            drain(&mut field).await?;
+            Ok(futures::future::ok(info).boxed_local())
+        }
+        .boxed_local()
+    }
+}
+
+/// Storage of raw bytes of a [`Probed`] image.
+pub trait ProbedStorage: Sized + 'static {
+    fn create() -> std::io::Result<Self>;
+    fn append(&mut self, chunk: &[u8]) -> std::io::Result<()>;
+
+    /// Appends `chunk` without blocking the executor, by default with [`append`](Self::append).
+    fn append_async(mut self, chunk: Bytes) -> LocalBoxFuture<'static, std::io::Result<Self>> {
+        futures::future::ready(self.append(&chunk).map(|_| self)).boxed_local()
+    }
+}
+
+impl ProbedStorage for Vec<u8> {
+    fn create() -> std::io::Result<Self> {
+        Ok(Vec::new())
+    }
+
+    fn append(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        self.extend_from_slice(chunk);
+        Ok(())
+    }
+}
+
+/// Written on actix's blocking thread pool.
+#[cfg(feature = "tempfile")]
+impl ProbedStorage for tempfile::NamedTempFile {
+    fn create() -> std::io::Result<Self> {
+        tempfile::NamedTempFile::new()
+    }
+
+    fn append(&mut self, chunk: &[u8]) -> std::io::Result<()> {
+        use std::io::Write;
+
+        self.write_all(chunk)
+    }
+
+    fn append_async(mut self, chunk: Bytes) -> LocalBoxFuture<'static, std::io::Result<Self>> {
+        let job = web::block(move || self.append(&chunk).map(|_| self));
+        async move {
+            job.await
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?
+        }
+        .boxed_local()
+    }
+}
+
+/// Image which isn't decoded, only its header is parsed. Raw bytes are kept in `T`,
+/// `Vec<u8>` or, with `tempfile` feature, `tempfile::NamedTempFile`.
+#[derive(Debug)]
+pub struct Probed<T = Vec<u8>> {
+    pub info: ImageInfo,
+    pub inner: T,
+}
+
+impl<T> Probed<T> {
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: ProbedStorage> FromField for Probed<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        let config = config.image.clone();
+        async move {
+            let mut field = field;
+            let (info, mut head) = ImageInfo::read_head(&mut field, &config).await?;
+            config.before_store(&mut head);
+
+            let mut inner = T::create()?.append_async(head.into()).await?;
+            while let Some(chunk) = field.next().await {
+                let chunk = chunk.map_err(actix_web::Error::from)?;
+                inner = inner.append_async(chunk).await?;
+            }
+            Ok(futures::future::ok(Self { info, inner }).boxed_local())
+        }
+        .boxed_local()
+    }
+}
+
+/// How an image is fitted into the size of [`Resize`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Downscales to fit inside, keeping aspect ratio. Smaller images are left as is.
+    Fit,
+    /// Scales to cover the whole size, keeping aspect ratio, and crops the center.
+    Fill,
+    /// Scales to exactly the size.
+    Exact,
+}
+
+/// Size to resize [`Resized`] and [`Thumbnail`] images to.
+#[derive(Clone, Copy, Debug)]
+pub struct Resize {
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Filter of [`Resized`] images, thumbnails are always sampled with a fast box filter
+    pub filter: FilterType,
+    pub mode: ResizeMode,
+}
+
+/// 256x256 fit with triangle filter.
+impl Default for Resize {
+    fn default() -> Self {
+        Self::new(256, 256)
+    }
+}
+
+impl Resize {
+    pub fn new(max_width: u32, max_height: u32) -> Self {
+        Self {
+            max_width,
+            max_height,
+            filter: FilterType::Triangle,
+            mode: ResizeMode::Fit,
+        }
+    }
+
+    pub fn filter(mut self, filter: FilterType) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn mode(mut self, mode: ResizeMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Smallest scale of a `width`x`height` image keeping enough pixels for the result.
+    fn scale(&self, width: u32, height: u32) -> f64 {
+        let sw = f64::from(self.max_width) / f64::from(width.max(1));
+        let sh = f64::from(self.max_height) / f64::from(height.max(1));
+        match self.mode {
+            ResizeMode::Fit => sw.min(sh),
+            ResizeMode::Fill | ResizeMode::Exact => sw.max(sh),
+        }
+    }
 
+    /// Numerator of `n / 8` scaling to decode a `width`x`height` JPEG at. Both orientations
+    /// are checked, as EXIF may turn the image.
+    fn dct_scale(&self, width: u32, height: u32) -> u8 {
+        let scale = self.scale(width, height).max(self.scale(height, width));
+        [1, 2, 4]
+            .iter()
+            .copied()
+            .find(|&n| f64::from(n) / 8.0 >= scale)
+            .unwrap_or(8)
+    }
+
+    fn apply<P>(
+        &self,
+        img: image::ImageBuffer<P, Vec<u8>>,
+        thumbnail: bool,
+    ) -> image::ImageBuffer<P, Vec<u8>>
+    where
+        P: image::Pixel<Subpixel = u8> + 'static,
+    {
+        let (w, h) = img.dimensions();
+        let scale = self.scale(w, h);
+        let (nw, nh) = match self.mode {
+            ResizeMode::Fit if scale >= 1.0 => return img,
+            ResizeMode::Exact => (self.max_width, self.max_height),
+            _ => (
+                ((f64::from(w) * scale).round() as u32).max(1),
+                ((f64::from(h) * scale).round() as u32).max(1),
+            ),
+        };
+
+        let img = if (nw, nh) == (w, h) {
+            img
+        } else if thumbnail {
+            image::imageops::thumbnail(&img, nw, nh)
+        } else {
+            image::imageops::resize(&img, nw, nh, self.filter)
+        };
+
+        match self.mode {
+            ResizeMode::Fill => {
+                let (cw, ch) = (self.max_width.min(nw), self.max_height.min(nh));
+                image::imageops::crop_imm(&img, (nw - cw) / 2, (nh - ch) / 2, cw, ch).to_image()
+            }
+            _ => img,
+        }
+    }
+}
+
+/// Image downscaled while decoding, by [`ImageConfig::resize`] or `#[awmpde(resize(...))]`.
+/// Left as is without a size.
+#[derive(Deref, DerefMut, Debug)]
+pub struct Resized<I>(pub I);
+
+/// Image shrunk with fast sampling, by [`ImageConfig::resize`] or `#[awmpde(resize(...))]`,
+/// 256x256 fit by default.
+#[derive(Deref, DerefMut, Debug)]
+pub struct Thumbnail<I>(pub I);
+
+/// Image of any `image` pixel type, e.g. `ImageBuffer<Rgb<u16>, Vec<u16>>` keeps 16 bits of
+/// PNG and TIFF images. Float pixels hold 8 or 16-bit values scaled to `0.0..=1.0`.
 #[derive(Deref, DerefMut, Debug)]
 pub struct ImageBuffer<P: image::Pixel, Cont>(pub image::ImageBuffer<P, Cont>);
 
+/// Pixel of an [`ImageBuffer`] field.
+pub trait DecodePixel: image::Pixel + Send + 'static
+where
+    Self::Subpixel: Send,
+{
+    /// Color type asked from decoders, `None` to keep what the image has
+    const COLOR: Option<ColorType>;
+
+    fn from_dynamic(img: image::DynamicImage) -> image::ImageBuffer<Self, Vec<Self::Subpixel>>;
+}
+
+/// Scales subpixels of `img` widened to 16 bits by `into` to `0.0..=1.0`.
+fn to_f32<P, Q>(
+    img: image::DynamicImage,
+    into: fn(image::DynamicImage) -> image::ImageBuffer<P, Vec<u16>>,
+) -> image::ImageBuffer<Q, Vec<f32>>
+where
+    P: image::Pixel<Subpixel = u16> + 'static,
+    Q: image::Pixel<Subpixel = f32> + 'static,
+{
+    let color = img.color();
+    // `image` widens 8-bit subpixels by shifting, so 255 becomes 0xff00
+    let max = if color.bytes_per_pixel() == color.channel_count() {
+        f32::from(0xff00u16)
+    } else {
+        f32::from(u16::MAX)
+    };
+    let img = into(img);
+    let (w, h) = img.dimensions();
+    let raw = img.into_raw().into_iter().map(|v| f32::from(v) / max);
+    image::ImageBuffer::from_raw(w, h, raw.collect()).expect("same channel count")
+}
+
+macro_rules! decode_pixel(
+	{ $pixel:ty, $color:expr, |$img:ident| $into:expr } => {
+		impl DecodePixel for $pixel {
+			const COLOR: Option<ColorType> = $color;
+
+			fn from_dynamic($img: image::DynamicImage) -> image::ImageBuffer<Self, Vec<Self::Subpixel>> {
+				$into
+			}
+		}
+	};
+);
+
+decode_pixel!(Luma<u8>, Some(ColorType::L8), |img| img.into_luma8());
+decode_pixel!(LumaA<u8>, Some(ColorType::La8), |img| img
+    .into_luma_alpha8());
+decode_pixel!(Rgb<u8>, Some(ColorType::Rgb8), |img| img.into_rgb8());
+decode_pixel!(Rgba<u8>, Some(ColorType::Rgba8), |img| img.into_rgba8());
+decode_pixel!(Bgr<u8>, Some(ColorType::Bgr8), |img| img.into_bgr8());
+decode_pixel!(Bgra<u8>, Some(ColorType::Bgra8), |img| img.into_bgra8());
+decode_pixel!(Luma<u16>, None, |img| img.into_luma16());
+decode_pixel!(LumaA<u16>, None, |img| img.into_luma_alpha16());
+decode_pixel!(Rgb<u16>, None, |img| img.into_rgb16());
+decode_pixel!(Rgba<u16>, None, |img| img.into_rgba16());
+decode_pixel!(Luma<f32>, None, |img| to_f32(img, |i| i.into_luma16()));
+decode_pixel!(LumaA<f32>, None, |img| to_f32(img, |i| i
+    .into_luma_alpha16()));
+decode_pixel!(Rgb<f32>, None, |img| to_f32(img, |i| i.into_rgb16()));
+decode_pixel!(Rgba<f32>, None, |img| to_f32(img, |i| i.into_rgba16()));
+
+impl<P> FromField for ImageBuffer<P, Vec<P::Subpixel>>
+where
+    P: DecodePixel,
+    P::Subpixel: Send,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        read_and_decode(field, &config.image, |vec, ct, config| {
+            let img = config.decode(&vec, &ct, P::COLOR, None)?;
+            Ok(Self(P::from_dynamic(img)))
+        })
+    }
+}
+
 #[derive(Deref, DerefMut)]
 pub struct DynamicImage(pub image::DynamicImage);
 
@@ -14,120 +1017,127 @@ impl FromField for DynamicImage {
     type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     fn from_field(field: actix_multipart::Field) -> Self::Future {
-        async move {
-            let ct = field.content_type().clone();
-            let vec = Vec::<u8>::from_field(field).await.unwrap();
-            let tp = match ct.subtype() {
-                mime::JPEG => Some(ImageFormat::Jpeg),
-                mime::PNG => Some(ImageFormat::Png),
-                mime::GIF => Some(ImageFormat::Gif),
-                mime::BMP => Some(ImageFormat::Bmp),
-                _ => None,
-            };
-            let cur = Cursor::new(&*vec);
-            let rdr = match tp {
-                Some(fmt) => ImgReader::with_format(cur, fmt),
-                None => ImgReader::new(cur)
-                    .with_guessed_format()
-                    .expect("Cursor io never fails"),
-            };
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
 
-            Ok(Self(rdr.decode()?))
-        }
-        .boxed_local()
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        read_and_decode(field, &config.image, |vec, ct, config| {
+            Ok(Self(config.decode(&vec, &ct, None, None)?))
+        })
     }
 }
 
 macro_rules! ff_img(
-	{ $ty:ident, $img:ty, $into:ident } => {
-		#[derive(Deref, DerefMut, Debug)]
+	{ $ty:ident, $img:ty, $color:ident, $into:ident } => {
+		#[derive(Deref, DerefMut, Debug, Clone)]
 		pub struct $ty(pub $img);
 
+		impl $ty {
+			fn decode(
+				vec: &[u8],
+				ct: &Mime,
+				config: &ImageConfig,
+				resize: Option<&Resize>,
+			) -> Result<$img, DecodeError> {
+				Ok(config.decode(vec, ct, Some(ColorType::$color), resize)?.$into())
+			}
+		}
+
+		ff_img_impls!($ty);
+	};
+);
+
+/// `FromField` of an image type and of its [`Resized`] and [`Thumbnail`] wrappers.
+macro_rules! ff_img_impls(
+	{ $ty:ident } => {
 		impl FromField for $ty {
 			type Error = Error;
 			type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
 			fn from_field(field: actix_multipart::Field) -> Self::Future {
-				async move {
-					let img = DynamicImage::from_field(field).await?;
-					let img = img.0.$into();
-					Ok(Self(img))
-				}
-				.boxed_local()
+				Self::from_field_deferred(field, &FieldConfig::default())
+					.and_then(|rest| rest)
+					.boxed_local()
 			}
-		}
-	};
-);
 
-#[cfg(feature = "mozjpeg")]
-macro_rules! ff_img_mozjpeg(
-	{ $ty:ident, $img:ty, $subp:ty, $into:ident, $into_img:ident } => {
-		#[derive(Deref, DerefMut, Debug, Clone)]
-		pub struct $ty(pub $img);
+			fn from_field_deferred(
+				field: actix_multipart::Field,
+				config: &FieldConfig,
+			) -> DeferredRead<Self> {
+				read_and_decode(field, &config.image, |vec, ct, config| {
+					Ok(Self($ty::decode(&vec, &ct, config, None)?))
+				})
+			}
+		}
 
-		impl FromField for $ty {
+		impl FromField for Resized<$ty> {
 			type Error = Error;
 			type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
 			fn from_field(field: actix_multipart::Field) -> Self::Future {
-				use mozjpeg::{decompress::DctMethod, Decompress, ALL_MARKERS};
-
-				if *field.content_type() == mime::IMAGE_JPEG {
-					async move {
-						let buf = Vec::<u8>::from_field(field).await.unwrap();
-						let mut decomp = Decompress::with_markers(ALL_MARKERS)
-							.from_mem(&buf[..])
-							.map_err(|_| Error::MozjpgDecodeError)?;
-						decomp.dct_method(DctMethod::IntegerFast);
-
-						let (w, h) = decomp.size();
-						let mut decomp = decomp.$into()
-							.map_err(|_| Error::MozjpgDecodeError)?;
-						let out = decomp
-							.read_scanlines::<$subp>()
-							.ok_or(Error::MozjpgDecodeError)?;
-
-						let out: &[$subp] = &*out;
-						let sz = out.len() * std::mem::size_of::<$subp>();
-						let out: &[u8] = unsafe {
-							std::slice::from_raw_parts(
-                                out.as_ptr() as *const u8,
-                                sz,
-                            )
-						};
-
-						Ok(Self(<$img>::from_raw(w as u32, h as u32, out.to_vec())
-										 .ok_or(Error::MozjpgDecodeError)?))
-					}
-					.boxed_local()
-				} else {
-					async move {
-						let img = DynamicImage::from_field(field).await?;
-						let img = img.0.$into_img();
-						Ok(Self(img))
-					}
+				Self::from_field_deferred(field, &FieldConfig::default())
+					.and_then(|rest| rest)
 					.boxed_local()
-				}
+			}
+
+			fn from_field_deferred(
+				field: actix_multipart::Field,
+				config: &FieldConfig,
+			) -> DeferredRead<Self> {
+				read_and_decode(field, &config.image, |vec, ct, config| {
+					let resize = config.resize.as_ref();
+					let img = $ty::decode(&vec, &ct, config, resize)?;
+					let img = match resize {
+						Some(resize) => resize.apply(img, false),
+						None => img,
+					};
+					Ok(Self($ty(img)))
+				})
 			}
 		}
-	};
-);
 
-#[cfg(feature = "mozjpeg")]
-ff_img_mozjpeg!(RgbImage, image::RgbImage, [u8; 3], rgb, into_rgb8);
-#[cfg(not(feature = "mozjpeg"))]
-ff_img!(RgbImage, image::RgbImage, into_rgb8);
+		impl FromField for Thumbnail<$ty> {
+			type Error = Error;
+			type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
-#[cfg(feature = "mozjpeg")]
-ff_img_mozjpeg!(RgbaImage, image::RgbaImage, [u8; 4], rgba, into_rgba8);
-#[cfg(not(feature = "mozjpeg"))]
-ff_img!(RgbaImage, image::RgbaImage, into_rgba8);
+			fn from_field(field: actix_multipart::Field) -> Self::Future {
+				Self::from_field_deferred(field, &FieldConfig::default())
+					.and_then(|rest| rest)
+					.boxed_local()
+			}
 
-#[cfg(feature = "mozjpeg")]
-ff_img_mozjpeg!(GrayImage, image::GrayImage, [u8; 1], grayscale, into_luma8);
-#[cfg(not(feature = "mozjpeg"))]
-ff_img!(GrayImage, image::GrayImage, into_luma8);
+			fn from_field_deferred(
+				field: actix_multipart::Field,
+				config: &FieldConfig,
+			) -> DeferredRead<Self> {
+				read_and_decode(field, &config.image, |vec, ct, config| {
+					let resize = config.resize.unwrap_or_default();
+					let img = $ty::decode(&vec, &ct, config, Some(&resize))?;
+					Ok(Self($ty(resize.apply(img, true))))
+				})
+			}
+		}
+	};
+);
 
-ff_img!(GrayAlphaImage, image::GrayAlphaImage, into_luma_alpha8);
-ff_img!(BgrImage, image::ImageBuffer<Bgr<u8>, Vec<u8>>, into_bgr8);
-ff_img!(BgraImage, image::ImageBuffer<Bgra<u8>, Vec<u8>>, into_bgra8);
+ff_img!(RgbImage, image::RgbImage, Rgb8, into_rgb8);
+ff_img!(RgbaImage, image::RgbaImage, Rgba8, into_rgba8);
+ff_img!(GrayImage, image::GrayImage, L8, into_luma8);
+ff_img!(GrayAlphaImage, image::GrayAlphaImage, La8, into_luma_alpha8);
+ff_img!(
+    BgrImage,
+    image::ImageBuffer<Bgr<u8>, Vec<u8>>,
+    Bgr8,
+    into_bgr8
+);
+ff_img!(
+    BgraImage,
+    image::ImageBuffer<Bgra<u8>, Vec<u8>>,
+    Bgra8,
+    into_bgra8
+);
@@ -0,0 +1,253 @@
+use super::*;
+
+use image::error::DecodingError;
+use image::{DynamicImage, ImageError, ImageResult};
+
+/// What the decoded image is used for, backends may decode straight to it.
+#[derive(Clone, Copy, Debug)]
+pub struct DecodeHint {
+    /// Color type the image is converted to afterwards, `None` to keep the decoded one
+    pub color: Option<ColorType>,
+    /// Image may be decoded at `scale / 8` of its size, 8 for full size
+    pub scale: u8,
+}
+
+/// Backend decoding images for [`ImageConfig`]. Limits are checked before it's called.
+pub trait ImageDecoder: Send + Sync {
+    /// Whether this backend decodes `format`, others are decoded by [`ImageRs`].
+    fn decodes(&self, format: ImageFormat) -> bool;
+
+    fn decode(
+        &self,
+        buf: &[u8],
+        format: ImageFormat,
+        hint: DecodeHint,
+    ) -> ImageResult<DynamicImage>;
+}
+
+/// Decoder of the `image` crate, for every format it's built with.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ImageRs;
+
+impl ImageDecoder for ImageRs {
+    fn decodes(&self, _format: ImageFormat) -> bool {
+        true
+    }
+
+    fn decode(
+        &self,
+        buf: &[u8],
+        format: ImageFormat,
+        _hint: DecodeHint,
+    ) -> ImageResult<DynamicImage> {
+        ImgReader::with_format(Cursor::new(buf), format).decode()
+    }
+}
+
+#[cfg_attr(not(any(feature = "mozjpeg", feature = "zune-jpeg")), allow(dead_code))]
+fn jpeg_error(e: impl ToString) -> ImageError {
+    ImageError::Decoding(DecodingError::new(ImageFormat::Jpeg.into(), e.to_string()))
+}
+
+/// How CMYK JPEGs are turned into RGB.
+#[cfg(feature = "mozjpeg")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CmykMode {
+    /// Inverted CMYK as written by Adobe software
+    Inverted,
+    Plain,
+    /// Fail to decode them
+    Reject,
+}
+
+#[cfg(feature = "mozjpeg")]
+pub use mozjpeg::decompress::DctMethod;
+
+/// Decoder of JPEGs with libjpeg, the default with `mozjpeg` feature.
+#[cfg(feature = "mozjpeg")]
+#[derive(Clone, Copy, Debug)]
+pub struct Mozjpeg {
+    pub dct_method: DctMethod,
+    pub fancy_upsampling: bool,
+    pub block_smoothing: bool,
+    pub cmyk: CmykMode,
+}
+
+/// Fast integer DCT, the rest as in libjpeg.
+#[cfg(feature = "mozjpeg")]
+impl Default for Mozjpeg {
+    fn default() -> Self {
+        Self {
+            dct_method: DctMethod::IntegerFast,
+            fancy_upsampling: true,
+            block_smoothing: true,
+            cmyk: CmykMode::Inverted,
+        }
+    }
+}
+
+#[cfg(feature = "mozjpeg")]
+impl Mozjpeg {
+    /// Reads all scanlines as pixels of `T` into bytes.
+    fn scanlines<T: Copy>(
+        mut decomp: mozjpeg::decompress::DecompressStarted<&[u8]>,
+    ) -> ImageResult<(u32, u32, Vec<u8>)> {
+        let (w, h) = (decomp.width() as u32, decomp.height() as u32);
+        let out = decomp
+            .read_scanlines::<T>()
+            .ok_or_else(|| jpeg_error("failed to read scanlines"))?;
+
+        let sz = out.len() * std::mem::size_of::<T>();
+        let out: &[u8] = unsafe { std::slice::from_raw_parts(out.as_ptr() as *const u8, sz) };
+        Ok((w, h, out.to_vec()))
+    }
+}
+
+#[cfg(feature = "mozjpeg")]
+impl ImageDecoder for Mozjpeg {
+    fn decodes(&self, format: ImageFormat) -> bool {
+        format == ImageFormat::Jpeg
+    }
+
+    fn decode(
+        &self,
+        buf: &[u8],
+        _format: ImageFormat,
+        hint: DecodeHint,
+    ) -> ImageResult<DynamicImage> {
+        use image::error::{UnsupportedError, UnsupportedErrorKind};
+        use mozjpeg::{ColorSpace, Decompress, ALL_MARKERS};
+
+        let mut decomp = Decompress::with_markers(ALL_MARKERS)
+            .from_mem(buf)
+            .map_err(jpeg_error)?;
+        decomp.dct_method(self.dct_method);
+        decomp.do_fancy_upsampling(self.fancy_upsampling);
+        decomp.do_block_smoothing(self.block_smoothing);
+        decomp.scale(hint.scale);
+
+        let native = decomp.color_space();
+        let image = |img: Option<DynamicImage>| img.ok_or_else(|| jpeg_error("bad image size"));
+
+        if let ColorSpace::JCS_CMYK | ColorSpace::JCS_YCCK = native {
+            let inverted = match self.cmyk {
+                CmykMode::Inverted => true,
+                CmykMode::Plain => false,
+                CmykMode::Reject => {
+                    return Err(ImageError::Unsupported(
+                        UnsupportedError::from_format_and_kind(
+                            ImageFormat::Jpeg.into(),
+                            UnsupportedErrorKind::GenericFeature("CMYK".to_string()),
+                        ),
+                    ))
+                }
+            };
+            let decomp = decomp
+                .to_colorspace(ColorSpace::JCS_CMYK)
+                .map_err(jpeg_error)?;
+            let (w, h, cmyk) = Self::scanlines::<[u8; 4]>(decomp)?;
+            let rgb = cmyk
+                .chunks_exact(4)
+                .flat_map(|p| cmyk_to_rgb(p, inverted))
+                .collect();
+            return image(image::RgbImage::from_raw(w, h, rgb).map(DynamicImage::ImageRgb8));
+        }
+
+        match (hint.color, native) {
+            (Some(ColorType::L8), _) | (None, ColorSpace::JCS_GRAYSCALE) => {
+                let decomp = decomp.grayscale().map_err(jpeg_error)?;
+                let (w, h, out) = Self::scanlines::<[u8; 1]>(decomp)?;
+                image(image::GrayImage::from_raw(w, h, out).map(DynamicImage::ImageLuma8))
+            }
+            (Some(ColorType::Rgba8), _) => {
+                let decomp = decomp.rgba().map_err(jpeg_error)?;
+                let (w, h, out) = Self::scanlines::<[u8; 4]>(decomp)?;
+                image(image::RgbaImage::from_raw(w, h, out).map(DynamicImage::ImageRgba8))
+            }
+            _ => {
+                let decomp = decomp.rgb().map_err(jpeg_error)?;
+                let (w, h, out) = Self::scanlines::<[u8; 3]>(decomp)?;
+                image(image::RgbImage::from_raw(w, h, out).map(DynamicImage::ImageRgb8))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "mozjpeg")]
+fn cmyk_to_rgb(p: &[u8], inverted: bool) -> [u8; 3] {
+    let ink = |v: u8| {
+        if inverted {
+            u16::from(v)
+        } else {
+            255 - u16::from(v)
+        }
+    };
+    let k = ink(p[3]);
+    [
+        (ink(p[0]) * k / 255) as u8,
+        (ink(p[1]) * k / 255) as u8,
+        (ink(p[2]) * k / 255) as u8,
+    ]
+}
+
+/// Decoder of JPEGs in pure Rust with `zune-jpeg`, which always turns CMYK to RGB itself.
+#[cfg(feature = "zune-jpeg")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZuneJpeg {
+    /// Fail on recoverable errors in the image
+    pub strict: bool,
+}
+
+#[cfg(feature = "zune-jpeg")]
+impl ImageDecoder for ZuneJpeg {
+    fn decodes(&self, format: ImageFormat) -> bool {
+        format == ImageFormat::Jpeg
+    }
+
+    fn decode(
+        &self,
+        buf: &[u8],
+        _format: ImageFormat,
+        hint: DecodeHint,
+    ) -> ImageResult<DynamicImage> {
+        use zune_jpeg::zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+
+        let out = match hint.color {
+            Some(ColorType::L8) => ColorSpace::Luma,
+            Some(ColorType::Rgba8) => ColorSpace::RGBA,
+            _ => ColorSpace::RGB,
+        };
+        // Size is already checked against `ImageLimits`
+        let options = DecoderOptions::default()
+            .set_strict_mode(self.strict)
+            .set_max_width(usize::from(u16::MAX))
+            .set_max_height(usize::from(u16::MAX))
+            .jpeg_set_out_colorspace(out);
+
+        let mut decoder = zune_jpeg::JpegDecoder::new_with_options(buf, options);
+        let pixels = decoder
+            .decode()
+            .map_err(|e| jpeg_error(format!("{:?}", e)))?;
+        let info = decoder.info().expect("headers are decoded");
+        let (w, h) = (u32::from(info.width), u32::from(info.height));
+
+        let img = match out {
+            ColorSpace::Luma => {
+                image::GrayImage::from_raw(w, h, pixels).map(DynamicImage::ImageLuma8)
+            }
+            ColorSpace::RGBA => {
+                image::RgbaImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgba8)
+            }
+            _ => image::RgbImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgb8),
+        };
+        img.ok_or_else(|| jpeg_error("bad image size"))
+    }
+}
+
+/// Decoder used when none is set, [`Mozjpeg`] with `mozjpeg` feature or [`ImageRs`].
+pub(crate) fn default_decoder() -> Arc<dyn ImageDecoder> {
+    #[cfg(feature = "mozjpeg")]
+    return Arc::new(Mozjpeg::default());
+    #[cfg(not(feature = "mozjpeg"))]
+    return Arc::new(ImageRs);
+}
@@ -0,0 +1,180 @@
+use super::*;
+
+use image::codecs::{gif::GifDecoder, png::PngDecoder};
+use image::AnimationDecoder;
+use std::time::Duration;
+
+/// What happens to a frame's area before the next frame is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Disposal {
+    /// Left as is
+    Keep,
+    /// Cleared to the background
+    Background,
+    /// Restored to what was there before the frame
+    Previous,
+}
+
+/// Frame of an animation, already drawn over the previous ones.
+#[derive(Clone, Debug)]
+pub struct Frame {
+    pub buffer: image::RgbaImage,
+    pub delay: Duration,
+    /// `None` if the format doesn't say
+    pub disposal: Option<Disposal>,
+}
+
+/// All frames of an animated GIF or APNG, a single frame for still images. Limited by
+/// `max_frames`, `max_total_pixels` and `max_alloc` of [`ImageLimits`].
+///
+/// `image` 0.23 can't decode animated WebP, those fail as unsupported.
+#[derive(Clone, Debug, Deref, DerefMut)]
+pub struct Frames(pub Vec<Frame>);
+
+impl Frames {
+    pub fn into_inner(self) -> Vec<Frame> {
+        self.0
+    }
+
+    fn decode(buf: &[u8], ct: &Mime, config: &ImageConfig) -> Result<Self, DecodeError> {
+        let format = config.detect_format(buf, ct)?;
+        check_limits(buf, format, &config.limits)?;
+
+        let frames = match format {
+            ImageFormat::Gif => GifDecoder::new(Cursor::new(buf))?.into_frames(),
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(Cursor::new(buf))?;
+                if !decoder.is_apng() {
+                    return Self::still(buf, ct, config);
+                }
+                decoder.apng().into_frames()
+            }
+            ImageFormat::WebP if is_animated(format, buf) => {
+                use image::error::{UnsupportedError, UnsupportedErrorKind};
+
+                let kind = UnsupportedErrorKind::GenericFeature("animation".to_string());
+                let e = UnsupportedError::from_format_and_kind(format.into(), kind);
+                return Err(image::ImageError::Unsupported(e).into());
+            }
+            _ => return Self::still(buf, ct, config),
+        };
+
+        let disposals = disposals(format, buf);
+        let mut out = Vec::new();
+        let mut pixels = 0u64;
+        for frame in frames {
+            let frame = frame?;
+            let (w, h) = frame.buffer().dimensions();
+            pixels += u64::from(w) * u64::from(h);
+            config.limits.check_frames(out.len() + 1, pixels)?;
+
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            out.push(Frame {
+                delay: Duration::from_micros(u64::from(numer) * 1000 / u64::from(denom.max(1))),
+                disposal: disposals.get(out.len()).copied(),
+                buffer: frame.into_buffer(),
+            });
+        }
+        Ok(Self(out))
+    }
+
+    fn still(buf: &[u8], ct: &Mime, config: &ImageConfig) -> Result<Self, DecodeError> {
+        let img = config.decode(buf, ct, Some(ColorType::Rgba8), None)?;
+        Ok(Self(vec![Frame {
+            buffer: img.into_rgba8(),
+            delay: Duration::default(),
+            disposal: None,
+        }]))
+    }
+}
+
+/// Disposal of every frame, read from GIF graphic control extensions or APNG `fcTL` chunks.
+fn disposals(format: ImageFormat, buf: &[u8]) -> Vec<Disposal> {
+    let mut out = Vec::new();
+    match format {
+        ImageFormat::Gif => gif_disposals(buf, &mut out),
+        ImageFormat::Png => apng_disposals(buf, &mut out),
+        _ => None,
+    };
+    out
+}
+
+fn gif_disposals(buf: &[u8], out: &mut Vec<Disposal>) -> Option<()> {
+    // Size of a color table from packed fields
+    let table = |flags: u8| {
+        if flags & 0x80 != 0 {
+            3 << ((flags & 7) + 1)
+        } else {
+            0
+        }
+    };
+    let skip_blocks = |mut pos: usize| loop {
+        let len = usize::from(*buf.get(pos)?);
+        pos += 1 + len;
+        if len == 0 {
+            return Some(pos);
+        }
+    };
+
+    // Header and logical screen descriptor
+    let mut pos = 13 + table(*buf.get(10)?);
+    let mut disposal = None;
+    loop {
+        match buf.get(pos)? {
+            0x21 => {
+                if buf.get(pos + 1..pos + 3)? == [0xf9, 4] {
+                    disposal = Some((buf.get(pos + 3)? >> 2) & 7);
+                }
+                pos = skip_blocks(pos + 2)?;
+            }
+            0x2c => {
+                out.push(match disposal.take() {
+                    Some(2) => Disposal::Background,
+                    Some(3) => Disposal::Previous,
+                    _ => Disposal::Keep,
+                });
+                // Image descriptor, local color table and LZW code size
+                pos += 10 + table(*buf.get(pos + 9)?) + 1;
+                pos = skip_blocks(pos)?;
+            }
+            _ => return Some(()),
+        }
+    }
+}
+
+fn apng_disposals(buf: &[u8], out: &mut Vec<Disposal>) -> Option<()> {
+    let mut chunks = buf.get(8..)?;
+    while chunks.len() >= 8 {
+        let len = u32::from_be_bytes([chunks[0], chunks[1], chunks[2], chunks[3]]) as usize;
+        if &chunks[4..8] == b"fcTL" {
+            out.push(match chunks.get(8 + 24)? {
+                1 => Disposal::Background,
+                2 => Disposal::Previous,
+                _ => Disposal::Keep,
+            });
+        }
+        // Length, type and crc
+        chunks = chunks.get(12 + len..)?;
+    }
+    Some(())
+}
+
+impl FromField for Frames {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        read_and_decode(field, &config.image, |vec, ct, config| {
+            Self::decode(&vec, &ct, config)
+        })
+    }
+}
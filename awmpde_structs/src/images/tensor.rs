@@ -0,0 +1,164 @@
+use super::*;
+
+pub use ndarray::{Array3, Array4, ShapeError};
+
+/// Order of axes of a [`Tensor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// Height, width, channel
+    Hwc,
+    /// Channel, height, width
+    Chw,
+}
+
+/// How images are turned into a [`Tensor`], part of [`ImageConfig`].
+///
+/// By default channels are scaled to `0.0..=1.0` and the image keeps its size.
+#[derive(Clone, Copy, Debug)]
+pub struct TensorConfig {
+    pub layout: Layout,
+    /// Subtracted from each RGB channel after scaling to `0.0..=1.0`
+    pub mean: [f32; 3],
+    /// Each RGB channel is divided by it last
+    pub std: [f32; 3],
+    /// Width and height images are scaled to, both above zero
+    pub size: Option<(u32, u32)>,
+    /// Keeps aspect ratio when scaling, padding with black
+    pub letterbox: bool,
+}
+
+impl Default for TensorConfig {
+    fn default() -> Self {
+        Self {
+            layout: Layout::Hwc,
+            mean: [0.0; 3],
+            std: [1.0; 3],
+            size: None,
+            letterbox: false,
+        }
+    }
+}
+
+impl TensorConfig {
+    pub fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn mean(mut self, mean: [f32; 3]) -> Self {
+        self.mean = mean;
+        self
+    }
+
+    pub fn std(mut self, std: [f32; 3]) -> Self {
+        self.std = std;
+        self
+    }
+
+    /// Scales images to `width`x`height`, panics if either is zero.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        assert!(width > 0 && height > 0, "tensor size can't be zero");
+        self.size = Some((width, height));
+        self
+    }
+
+    pub fn letterbox(mut self) -> Self {
+        self.letterbox = true;
+        self
+    }
+
+    fn scale(&self, img: image::RgbImage) -> image::RgbImage {
+        use image::imageops::{replace, resize};
+
+        let (width, height) = match self.size {
+            Some(size) => size,
+            None => return img,
+        };
+        if !self.letterbox {
+            return Resize::new(width, height)
+                .mode(ResizeMode::Exact)
+                .apply(img, false);
+        }
+
+        let (w, h) = img.dimensions();
+        let scale = (f64::from(width) / f64::from(w)).min(f64::from(height) / f64::from(h));
+        let nw = ((f64::from(w) * scale).round() as u32).clamp(1, width);
+        let nh = ((f64::from(h) * scale).round() as u32).clamp(1, height);
+
+        let mut canvas = image::RgbImage::new(width, height);
+        replace(
+            &mut canvas,
+            &resize(&img, nw, nh, FilterType::Triangle),
+            (width - nw) / 2,
+            (height - nh) / 2,
+        );
+        canvas
+    }
+
+    fn array(&self, img: &image::RgbImage) -> Array3<f32> {
+        let (w, h) = img.dimensions();
+        let value = |y: usize, x: usize, c: usize| {
+            let v = f32::from(img.get_pixel(x as u32, y as u32)[c]) / 255.0;
+            (v - self.mean[c]) / self.std[c]
+        };
+
+        match self.layout {
+            Layout::Hwc => {
+                Array3::from_shape_fn((h as usize, w as usize, 3), |(y, x, c)| value(y, x, c))
+            }
+            Layout::Chw => {
+                Array3::from_shape_fn((3, h as usize, w as usize), |(c, y, x)| value(y, x, c))
+            }
+        }
+    }
+}
+
+/// Image as normalized RGB `f32` tensor, shaped by [`TensorConfig`].
+#[derive(Clone, Debug, Deref, DerefMut)]
+pub struct Tensor(pub Array3<f32>);
+
+impl Tensor {
+    pub fn into_inner(self) -> Array3<f32> {
+        self.0
+    }
+
+    /// Stacks tensors of the same shape into a batch, e.g. of a `Vec<Tensor>` field, which
+    /// collects every part of the field's name with its tensor options.
+    pub fn stack(tensors: &[Tensor]) -> Result<Array4<f32>, ShapeError> {
+        let views = tensors.iter().map(|t| t.0.view()).collect::<Vec<_>>();
+        ndarray::stack(ndarray::Axis(0), &views)
+    }
+}
+
+impl FromField for Tensor {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_field(field: actix_multipart::Field) -> Self::Future {
+        Self::from_field_deferred(field, &FieldConfig::default())
+            .and_then(|rest| rest)
+            .boxed_local()
+    }
+
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> DeferredRead<Self> {
+        read_and_decode(field, &config.image, |vec, ct, config| {
+            let tensor = &config.tensor;
+            // Decode no smaller than needed for the target size
+            let resize = tensor.size.map(|(w, h)| {
+                let mode = if tensor.letterbox {
+                    ResizeMode::Fit
+                } else {
+                    ResizeMode::Exact
+                };
+                Resize::new(w, h).mode(mode)
+            });
+            let img = config
+                .decode(&vec, &ct, Some(ColorType::Rgb8), resize.as_ref())?
+                .into_rgb8();
+            Ok(Self(tensor.array(&tensor.scale(img))))
+        })
+    }
+}
@@ -3,6 +3,9 @@ extern crate derive_deref;
 
 #[cfg(feature = "chrono")]
 pub mod chrono_types;
+pub mod de;
+#[cfg(feature = "exif")]
+pub mod exif_field;
 pub mod images;
 #[cfg(feature = "test")]
 pub mod test;
@@ -11,6 +14,12 @@ pub mod uuid_field;
 
 mod basic;
 pub use basic::*;
+mod formats;
+pub use formats::*;
+mod reader;
+pub use reader::*;
+mod stream;
+pub use stream::*;
 
 use actix_web::{dev::Payload, http::StatusCode, FromRequest, HttpRequest};
 use displaydoc::Display;
@@ -26,39 +35,111 @@ use std::future::Future;
 use std::io::Cursor;
 use std::marker::PhantomData;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-#[derive(Debug, Error, Display)]
-pub enum Error {
-    /// Failed to deserialize
-    SerializationError(#[from] serde_json::error::Error),
-    /// Failed to decode image
-    ImageDecodeError(#[from] image::error::ImageError),
-    /// Failed to decode jpeg image
-    MozjpgDecodeError,
-    /// No such field in request `{0}'
-    NoFieldError(String),
-    /// No such filename for file
-    NoFilenameError,
-    /// Filename must be valid UTF8
-    FilenameUTF8Error,
-    /// Failed to parse UTF8 string
-    StringDecodeError(#[from] std::string::FromUtf8Error),
-    /// {0}
-    ActixWebError(#[from] actix_web::error::Error),
-    /// Failed to find field {0:?} in request
-    FieldError(&'static str),
-    /// Unknown Error. Usually for empty error type
-    UnknownError,
-
-    #[cfg(feature = "uuid")]
-    /// Failed to parse UUID
-    UUIDParseError(#[from] uuid::Error),
+pub use error::Error;
+
+mod error {
+    // Impls derived for `Error` still use its deprecated variants
+    #![allow(deprecated)]
+
+    use super::*;
+
+    #[derive(Debug, Error, Display)]
+    pub enum Error {
+        /// Failed to deserialize
+        SerializationError(#[from] serde_json::error::Error),
+        /// Failed to deserialize: {0}
+        DeserializeError(#[from] serde::de::value::Error),
+        /// Failed to decode image
+        ImageDecodeError(#[from] image::error::ImageError),
+        /// Failed to decode jpeg image
+        #[deprecated(
+            since = "0.8.0",
+            note = "never returned, `Mozjpeg` gives `ImageDecodeError`"
+        )]
+        MozjpgDecodeError,
+        /// Image of {0}x{1} pixels is over the size limit
+        ImageTooLarge(u32, u32),
+        /// Content type `{0}' doesn't match image format {1:?}
+        ImageFormatMismatch(Mime, image::ImageFormat),
+        /// Image format {0:?} isn't allowed
+        ImageFormatNotAllowed(image::ImageFormat),
+        /// Animated images aren't allowed
+        ImageAnimated,
+        /// Animation has too many frames or pixels
+        AnimationTooLarge,
+        /// No such field in request `{0}'
+        NoFieldError(String),
+        /// No such filename for file
+        NoFilenameError,
+        /// Filename must be valid UTF8
+        FilenameUTF8Error,
+        /// Failed to parse UTF8 string
+        StringDecodeError(#[from] std::string::FromUtf8Error),
+        /// Failed to parse value: {0}
+        ParseError(String),
+        /// Unknown variant `{0}'
+        UnknownVariantError(String),
+        /// Unsupported content type `{0}'
+        UnsupportedMediaType(Mime),
+        /// Field is larger than {0} bytes
+        FieldTooLarge(usize),
+        /// Multipart body is larger than {0} bytes
+        BodyTooLarge(usize),
+        /// Multipart body has more than {0} parts
+        TooManyParts(usize),
+        /// {0}
+        ActixWebError(#[from] actix_web::error::Error),
+        /// Failed to find field {0:?} in request
+        FieldError(&'static str),
+        /// Field {0:?} was sent more than once
+        DuplicateFieldError(&'static str),
+        /// I/O error: {0}
+        IoError(#[source] std::io::Error),
+        /// Unknown Error. Usually for empty error type
+        UnknownError,
+
+        #[cfg(feature = "uuid")]
+        /// Failed to parse UUID
+        UUIDParseError(#[from] uuid::Error),
+        #[cfg(feature = "toml")]
+        /// Failed to deserialize toml
+        TomlError(#[from] toml::de::Error),
+        #[cfg(feature = "yaml")]
+        /// Failed to deserialize yaml
+        YamlError(#[from] serde_yaml::Error),
+        #[cfg(feature = "xml")]
+        /// Failed to deserialize xml
+        XmlError(#[from] quick_xml::DeError),
+        #[cfg(feature = "msgpack")]
+        /// Failed to deserialize MessagePack
+        MsgPackError(#[from] rmp_serde::decode::Error),
+        #[cfg(feature = "cbor")]
+        /// Failed to deserialize CBOR
+        CborError(#[from] serde_cbor::Error),
+        #[cfg(feature = "protobuf")]
+        /// Failed to decode protobuf
+        ProtobufError(#[from] prost::DecodeError),
+        #[cfg(feature = "exif")]
+        /// Failed to parse EXIF: {0}
+        ExifError(#[from] exif::Error),
+    }
 }
 
 impl actix_web::error::ResponseError for Error {
     fn status_code(&self) -> StatusCode {
         match self {
             Error::ActixWebError(e) => e.as_response_error().status_code(),
+            Error::UnsupportedMediaType(_)
+            | Error::ImageFormatNotAllowed(_)
+            | Error::ImageAnimated => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::FieldTooLarge(_)
+            | Error::BodyTooLarge(_)
+            | Error::TooManyParts(_)
+            | Error::ImageTooLarge(..)
+            | Error::AnimationTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::IoError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::BAD_REQUEST,
         }
     }
@@ -73,6 +154,16 @@ impl actix_web::error::ResponseError for Error {
     }
 }
 
+impl std::convert::From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        match err.get_ref().and_then(|e| e.downcast_ref::<ReadError>()) {
+            Some(ReadError::TooLarge(limit)) => Error::FieldTooLarge(*limit),
+            Some(ReadError::Multipart(e)) => Error::ParseError(e.clone()),
+            None => Error::IoError(err),
+        }
+    }
+}
+
 impl std::convert::From<Infallible> for Error {
     fn from(err: Infallible) -> Self {
         match err {}
@@ -82,10 +173,17 @@ impl std::convert::From<Infallible> for Error {
 pub struct Multipart<T> {
     /// Actual multipart
     pub mp: actix_multipart::Multipart,
+    /// Request the multipart came with
+    pub req: HttpRequest,
     /// Marker of phantomdata
     pub _marker: PhantomData<T>,
 }
 
+/// Rest of parsing a field which doesn't need the field anymore.
+pub type Deferred<T, E = Error> = LocalBoxFuture<'static, Result<T, E>>;
+/// Future reading a field, resolving to the rest of parsing.
+pub type DeferredRead<T, E = Error> = LocalBoxFuture<'static, Result<Deferred<T, E>, E>>;
+
 pub trait FromField: Sized {
     /// The associated error which can be returned.
     type Error: Into<actix_web::Error>;
@@ -93,40 +191,86 @@ pub trait FromField: Sized {
     type Future: Future<Output = Result<Self, Self::Error>> + 'static;
 
     fn from_field(field: actix_multipart::Field) -> Self::Future;
+
+    /// Reads the field and returns the rest of parsing, such as decoding an image on a thread
+    /// pool. Derived structs read next parts while it's running.
+    fn from_field_deferred(
+        field: actix_multipart::Field,
+        _config: &FieldConfig,
+    ) -> DeferredRead<Self, Self::Error>
+    where
+        Self: 'static,
+        Self::Error: 'static,
+    {
+        Self::from_field(field)
+            .map(|res| res.map(|v| futures::future::ok(v).boxed_local()))
+            .boxed_local()
+    }
+}
+
+/// Reads `field` with [`FromField::from_field_deferred`].
+pub async fn read_deferred<T>(
+    field: actix_multipart::Field,
+    config: &FieldConfig,
+) -> Result<Deferred<T>, Error>
+where
+    T: FromField + 'static,
+    T::Error: 'static,
+    Error: From<T::Error>,
+{
+    let rest = T::from_field_deferred(field, config).await?;
+    Ok(rest.map(|res| res.map_err(Error::from)).boxed_local())
+}
+
+/// Settings of parsing fields, register it with `App::app_data`.
+#[derive(Clone, Default)]
+pub struct FieldConfig {
+    /// Decoding of images
+    pub image: images::ImageConfig,
 }
 
 /// Trait which implements macro for your structures
-///
-/// FromRequest won't do because of static construction of future (static lifetimes).
-///
-/// We need to have lifetime because we depend upon multipart and branch into
-/// parsing different fields.
-pub trait FromMultipart<'a>: Sized {
+pub trait FromMultipart: Sized {
     /// The associated error which can be returned.
     type Error: Into<actix_web::Error>;
     /// Future that resolves to a Self
-    type Future: Future<Output = Result<Self, Self::Error>> + 'a;
+    type Future: Future<Output = Result<Self, Self::Error>> + 'static;
 
-    fn from_multipart(mp: actix_multipart::Multipart) -> Self::Future;
+    fn from_multipart(req: &HttpRequest, mp: actix_multipart::Multipart) -> Self::Future;
 }
 
-impl<'a, T: FromMultipart<'a>> Multipart<T> {
+/// Trait for structures whose parts can be parsed one at a time, see [`MultipartStream`].
+pub trait FromMultipartPart {
+    /// A parsed part of the structure, usually an enum with a variant per field.
+    type Part;
+
+    fn from_part(
+        field: actix_multipart::Field,
+        config: &FieldConfig,
+    ) -> LocalBoxFuture<'static, Result<Self::Part, Error>>;
+}
+
+impl<T: FromMultipart> Multipart<T> {
     #[inline]
     pub async fn into_inner(self) -> Result<T, Error> {
-        Ok(T::from_multipart(self.mp).await.map_err(|e| e.into())?)
+        Ok(T::from_multipart(&self.req, self.mp)
+            .await
+            .map_err(|e| e.into())?)
     }
 }
 
-impl<'a, T: Sized> FromRequest for Multipart<T> {
+impl<T: Sized> FromRequest for Multipart<T> {
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     #[inline]
     fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
         let mp = actix_multipart::Multipart::from_request(req, payload);
+        let req = req.clone();
         async {
             Ok(Self {
                 mp: mp.await?,
+                req,
                 _marker: Default::default(),
             })
         }
@@ -134,71 +278,355 @@ impl<'a, T: Sized> FromRequest for Multipart<T> {
     }
 }
 
-/// Type for annotating unwrapping of FormOrMultipartFuture by
-/// `form_or_multipart_unwrap`.
+type ErrorHandler = Arc<dyn Fn(Error, &HttpRequest) -> actix_web::Error + Send + Sync>;
+
+/// Configuration of [`MultipartForm`], register it with `App::app_data`.
+#[derive(Clone, Default)]
+pub struct MultipartFormConfig {
+    err_handler: Option<ErrorHandler>,
+}
+
+impl MultipartFormConfig {
+    /// Sets custom handler for errors of parsing the multipart.
+    pub fn error_handler<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Error, &HttpRequest) -> actix_web::Error + Send + Sync + 'static,
+    {
+        self.err_handler = Some(Arc::new(f));
+        self
+    }
+}
+
+/// Extractor parsing the whole multipart into `T` before the handler is called.
+///
+/// Errors go through [`MultipartFormConfig::error_handler`] if one is registered.
+#[derive(Deref, DerefMut, Debug, Clone, Copy)]
+pub struct MultipartForm<T>(pub T);
+
+impl<T> MultipartForm<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> FromRequest for MultipartForm<T>
+where
+    T: FromMultipart + 'static,
+    Error: From<T::Error>,
+{
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let mp = actix_multipart::Multipart::from_request(req, payload);
+        let req = req.clone();
+        async move {
+            let res = match mp.await {
+                Ok(mp) => T::from_multipart(&req, mp).await.map_err(Error::from),
+                Err(e) => Err(Error::ActixWebError(e)),
+            };
+
+            res.map(Self).map_err(|e| {
+                match req
+                    .app_data::<MultipartFormConfig>()
+                    .and_then(|cfg| cfg.err_handler.as_ref())
+                {
+                    Some(handler) => handler(e, &req),
+                    None => e.into(),
+                }
+            })
+        }
+        .boxed_local()
+    }
+}
+
+/// Type for parsing multipart with serde instead of `FromActixMultipart`.
+///
+/// `Multipart<Serde<T>>`, `MultipartForm<Serde<T>>` and `FormOrMultipart<Serde<T>>` accept
+/// any `T: Deserialize`, see [`de`] for how parts are mapped. Every part is buffered and fails
+/// with `413 Payload Too Large` once it's over `LIMIT` bytes, or once all of them are over
+/// `TOTAL` bytes or [`de::MAX_PARTS`] parts.
+///
+/// `T` has to be wrapped, as `FromMultipart` for every `T: Deserialize` would conflict with
+/// the derived one of structs deriving both.
+#[derive(Deref, DerefMut, Debug, Clone, Copy, serde::Deserialize)]
+#[serde(transparent)]
+pub struct Serde<T, const LIMIT: usize = { 1 << 20 }, const TOTAL: usize = { 16 << 20 }>(pub T);
+
+impl<T, const LIMIT: usize, const TOTAL: usize> FromMultipart for Serde<T, LIMIT, TOTAL>
+where
+    T: DeserializeOwned + 'static,
+{
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_multipart(_req: &HttpRequest, mp: actix_multipart::Multipart) -> Self::Future {
+        de::from_multipart(mp, LIMIT, TOTAL)
+            .map(|res| res.map(Self))
+            .boxed_local()
+    }
+}
+
+/// Extractor accepting both urlencoded and multipart requests, parsed into `T`
+/// before the handler is called.
 #[derive(Deref, DerefMut, Debug, Clone, Copy, Display)]
 pub struct FormOrMultipart<T>(pub T);
 
+impl<T> FormOrMultipart<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: FromMultipart + 'static> FromRequest for FormOrMultipart<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let fut = FormOrMultipartFuture::<T>::from_request(req, payload);
+        async move { Ok(Self(fut.await?.into_inner().await?)) }.boxed_local()
+    }
+}
+
 /// Type for accepting request both with types urlencoded and multipart.
+///
+/// Unlike [`FormOrMultipart`] leaves parsing of multipart to [`Self::into_inner`].
+///
+/// Requests other than multipart are turned into multipart with a part per key, so all of
+/// them are parsed by the same `FromMultipart` implementation:
+/// - `application/x-www-form-urlencoded` and `text/plain` forms give a part per pair.
+/// - `application/json` or `application/*+json` object gives a part per key. Strings become
+///   `text/plain` parts, other values `application/json` ones and `null`s are skipped. Arrays
+///   give a part per item, for `Vec<T>` fields, so a single-valued field such as
+///   `Json<Vec<T>>` can't be filled from a json array.
+///
+/// Any other content type fails with `415 Unsupported Media Type`.
 pub enum FormOrMultipartFuture<T> {
     /// url encoded form
-    Form(actix_web::web::Form<T>),
+    Form(Multipart<T>),
     /// multipart request
     Multipart(Multipart<T>),
+    /// json object
+    Json(Multipart<T>),
+    /// form with `text/plain` encoding
+    Text(Multipart<T>),
 }
 
-impl<'a, T: FromMultipart<'a>> FormOrMultipartFuture<T> {
+impl<T: FromMultipart> FormOrMultipartFuture<T> {
     /// If type match returns inner type
     pub async fn into_inner(self) -> Result<T, Error> {
-        Ok(match self {
-            Self::Form(f) => f.into_inner(),
-            Self::Multipart(m) => m.into_inner().await?,
-        })
+        match self {
+            Self::Form(m) | Self::Multipart(m) | Self::Json(m) | Self::Text(m) => {
+                m.into_inner().await
+            }
+        }
     }
 }
 
-impl<T: DeserializeOwned + 'static> FromRequest for FormOrMultipartFuture<T> {
+impl<T> Multipart<T> {
+    fn from_pairs<K, V>(req: HttpRequest, pairs: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<[u8]>,
+    {
+        Self {
+            mp: multipart_from_pairs(pairs),
+            req,
+            _marker: PhantomData,
+        }
+    }
+
+    fn from_parts(req: HttpRequest, parts: Vec<(String, Vec<u8>, Mime)>) -> Self {
+        Self {
+            mp: multipart_from_parts(parts),
+            req,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Part per key of a json object, with strings as text and other values as json.
+fn json_parts(obj: serde_json::Map<String, serde_json::Value>) -> Vec<(String, Vec<u8>, Mime)> {
+    use serde_json::Value;
+
+    fn part(k: String, v: Value) -> (String, Vec<u8>, Mime) {
+        match v {
+            Value::String(s) => (k, s.into_bytes(), mime::TEXT_PLAIN_UTF_8),
+            v => (k, v.to_string().into_bytes(), mime::APPLICATION_JSON),
+        }
+    }
+
+    let mut parts = Vec::new();
+    for (k, v) in obj {
+        match v {
+            Value::Null => {}
+            Value::Array(items) => parts.extend(items.into_iter().map(|v| part(k.clone(), v))),
+            v => parts.push(part(k, v)),
+        }
+    }
+    parts
+}
+
+/// Parses body of form with `text/plain` enctype, which is `key=value` lines.
+fn text_pairs(body: &[u8]) -> Result<Vec<(String, String)>, Error> {
+    let body = std::str::from_utf8(body).map_err(|e| Error::ParseError(e.to_string()))?;
+
+    Ok(body
+        .split('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .filter(|l| !l.is_empty())
+        .map(|l| match l.split_once('=') {
+            Some((k, v)) => (k.to_string(), v.to_string()),
+            None => (l.to_string(), String::new()),
+        })
+        .collect())
+}
+
+impl<T: 'static> FromRequest for FormOrMultipartFuture<T> {
     type Error = Error;
     type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
 
     #[inline]
     fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
-        use actix_web::HttpMessage;
-
-        let cont_type: &str = &req.content_type().to_lowercase();
-        if cont_type == "application/x-www-form-urlencoded" {
-            actix_web::web::Form::from_request(req, payload)
-                .map(move |res| Ok(Self::Form(res?)))
-                .boxed_local()
-        } else {
-            Multipart::from_request(req, payload)
+        use actix_web::{web, HttpMessage};
+
+        let mime = match req.mime_type() {
+            Ok(Some(mime)) => mime,
+            Ok(None) => mime::APPLICATION_OCTET_STREAM,
+            Err(e) => return futures::future::err(Error::ActixWebError(e.into())).boxed_local(),
+        };
+        let req = req.clone();
+
+        match (mime.type_(), mime.subtype()) {
+            (mime::APPLICATION, mime::WWW_FORM_URLENCODED) => {
+                web::Form::<Vec<(String, String)>>::from_request(&req, payload)
+                    .map(move |res| Ok(Self::Form(Multipart::from_pairs(req, res?.into_inner()))))
+                    .boxed_local()
+            }
+            (mime::MULTIPART, _) => Multipart::from_request(&req, payload)
                 .map(move |res| Ok(Self::Multipart(res?)))
-                .boxed_local()
+                .boxed_local(),
+            (mime::APPLICATION, subtype)
+                if subtype == mime::JSON || mime.suffix() == Some(mime::JSON) =>
+            {
+                web::Json::<serde_json::Map<String, serde_json::Value>>::from_request(&req, payload)
+                    .map(move |res| {
+                        let parts = json_parts(res?.into_inner());
+                        Ok(Self::Json(Multipart::from_parts(req, parts)))
+                    })
+                    .boxed_local()
+            }
+            (mime::TEXT, mime::PLAIN) => web::Bytes::from_request(&req, payload)
+                .map(move |res| {
+                    let pairs = text_pairs(&res?)?;
+                    Ok(Self::Text(Multipart::from_pairs(req, pairs)))
+                })
+                .boxed_local(),
+            _ => futures::future::err(Error::UnsupportedMediaType(mime)).boxed_local(),
         }
     }
 }
 
+/// Encodes key/value pairs as a multipart body with a text part per pair.
+pub fn multipart_from_pairs<K, V>(
+    pairs: impl IntoIterator<Item = (K, V)>,
+) -> actix_multipart::Multipart
+where
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+{
+    multipart_from_parts(
+        pairs
+            .into_iter()
+            .map(|(k, v)| (k, v, mime::TEXT_PLAIN_UTF_8)),
+    )
+}
+
+/// Encodes key/value pairs as a multipart body with a part of the given type per pair.
+fn multipart_from_parts<K, V>(
+    parts: impl IntoIterator<Item = (K, V, Mime)>,
+) -> actix_multipart::Multipart
+where
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+{
+    use actix_web::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use actix_web::web::Bytes;
+
+    let pairs = parts.into_iter().collect::<Vec<_>>();
+
+    // Boundary must not occur in any of the values
+    let mut boundary = String::from("awmpde-form-boundary-7f4c1d9e");
+    while pairs.iter().any(|(_, v, _)| {
+        v.as_ref()
+            .windows(boundary.len())
+            .any(|w| w == boundary.as_bytes())
+    }) {
+        boundary.push('x');
+    }
+
+    let mut body = Vec::new();
+    for (k, v, mime) in &pairs {
+        // `get_content_disposition` can't handle quotes and semicolons in names
+        let name = k
+            .as_ref()
+            .replace('%', "%25")
+            .replace('"', "%22")
+            .replace(';', "%3B")
+            .replace('\r', "%0D")
+            .replace('\n', "%0A");
+
+        body.extend_from_slice(b"--");
+        body.extend_from_slice(boundary.as_bytes());
+        body.extend_from_slice(b"\r\nContent-Disposition: form-data; name=\"");
+        body.extend_from_slice(name.as_bytes());
+        body.extend_from_slice(b"\"\r\nContent-Type: ");
+        body.extend_from_slice(mime.as_ref().as_bytes());
+        body.extend_from_slice(b"\r\n\r\n");
+        body.extend_from_slice(v.as_ref());
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(b"--");
+    body.extend_from_slice(boundary.as_bytes());
+    body.extend_from_slice(b"--\r\n");
+
+    let mut headers = HeaderMap::new();
+    let ct = format!("multipart/form-data; boundary={}", boundary);
+    headers.insert(CONTENT_TYPE, HeaderValue::from_str(&ct).unwrap());
+
+    let body = futures::stream::once(futures::future::ready(Ok(Bytes::from(body))));
+    actix_multipart::Multipart::new(&headers, body)
+}
+
+/// Encodes the request's query string as a multipart body, see [`multipart_from_pairs`].
+pub fn query_multipart(req: &HttpRequest) -> Result<actix_multipart::Multipart, Error> {
+    let pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(req.query_string())
+        .map_err(|e| Error::ParseError(e.to_string()))?;
+    Ok(multipart_from_pairs(pairs))
+}
+
 // TODO: doesn't assume UTF8
 pub fn get_content_disposition(field: &actix_multipart::Field) -> HashMap<Box<str>, Box<str>> {
     let mut out = HashMap::new();
     let disp = field
         .headers()
         .get("content-disposition")
-        .expect("Multipart always should have content-disposition")
-        .to_str()
-        .expect("TODO: for now assume `content-disposition' is UTF8");
-
-    let mut splt = disp.split(';').map(|f| f.trim());
-    assert_eq!(splt.next().unwrap(), "form-data");
-
-    for f in splt {
-        let vec = f.splitn(2, '=').collect::<Vec<_>>();
-        let k = vec[0];
-        let v = vec[1]
-            .strip_prefix('\"')
-            .unwrap()
-            .strip_suffix('\"')
-            .unwrap();
+        .map_or(Default::default(), |disp| {
+            String::from_utf8_lossy(disp.as_bytes())
+        });
+
+    // Parameters after the disposition type, malformed ones are skipped
+    for f in disp.split(';').skip(1).map(|f| f.trim()) {
+        let (k, v) = match f.find('=') {
+            Some(i) => (&f[..i], &f[i + 1..]),
+            None => continue,
+        };
+        let v = v
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(v);
 
         out.insert(
             k.to_string().into_boxed_str(),
@@ -0,0 +1,119 @@
+use super::*;
+
+use actix_multipart::{Field, MultipartError};
+use actix_web::web::{Buf, Bytes};
+use futures::{ready, Stream};
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Error inside the `io::Error`s returned by [`FieldReader`].
+#[derive(Debug, Error, Display)]
+pub(crate) enum ReadError {
+    /// Field is larger than {0} bytes
+    TooLarge(usize),
+    /// {0}
+    Multipart(String),
+}
+
+/// `AsyncRead` and `AsyncBufRead` over a multipart field, such as [`Field`] or
+/// [`FieldStream`].
+///
+/// Counts bytes read and fails with `InvalidData` once the field gets over the limit. The
+/// `io::Error`s it returns turn back into [`Error::FieldTooLarge`] or [`Error::ParseError`]
+/// with `?`, so they keep their status code.
+pub struct FieldReader<S = Field> {
+    stream: S,
+    chunk: Bytes,
+    received: usize,
+    limit: Option<usize>,
+}
+
+impl<S> FieldReader<S>
+where
+    S: Stream<Item = Result<Bytes, MultipartError>> + Unpin,
+{
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            chunk: Bytes::new(),
+            received: 0,
+            limit: None,
+        }
+    }
+
+    /// Fails reading once the field is over `limit` bytes.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Number of bytes read so far.
+    pub fn bytes_read(&self) -> usize {
+        self.received - self.chunk.len()
+    }
+
+    /// Returns the underlying stream, dropping any buffered bytes.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> AsyncBufRead for FieldReader<S>
+where
+    S: Stream<Item = Result<Bytes, MultipartError>> + Unpin,
+{
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        while this.chunk.is_empty() {
+            match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+                Some(Ok(chunk)) => {
+                    this.received += chunk.len();
+                    if let Some(limit) = this.limit.filter(|limit| this.received > *limit) {
+                        let e = ReadError::TooLarge(limit);
+                        return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, e)));
+                    }
+                    this.chunk = chunk;
+                }
+                Some(Err(e)) => {
+                    let e = ReadError::Multipart(e.to_string());
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, e)));
+                }
+                None => break,
+            }
+        }
+
+        Poll::Ready(Ok(&this.chunk))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().chunk.advance(amt)
+    }
+}
+
+impl<S> AsyncRead for FieldReader<S>
+where
+    S: Stream<Item = Result<Bytes, MultipartError>> + Unpin,
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let data = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let n = data.len().min(buf.remaining());
+        buf.put_slice(&data[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl FieldStream {
+    /// Reader over the rest of the field.
+    pub fn into_reader(self) -> FieldReader<Self> {
+        FieldReader::new(self)
+    }
+}
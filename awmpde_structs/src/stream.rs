@@ -0,0 +1,115 @@
+use super::*;
+
+use actix_multipart::{Field, MultipartError};
+use actix_web::http::header::HeaderMap;
+use actix_web::web::Bytes;
+use futures::{ready, Stream};
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Last field of a multipart struct left unread, to be streamed by the handler.
+///
+/// Only allowed as the last field of `FromActixMultipart` structs. Parts after it in the
+/// request are never read.
+pub struct FieldStream {
+    field: Field,
+    // Field can only be read while the multipart it came from is alive
+    _mp: actix_multipart::Multipart,
+}
+
+impl FieldStream {
+    pub fn new(field: Field, mp: actix_multipart::Multipart) -> Self {
+        Self { field, _mp: mp }
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        self.field.headers()
+    }
+
+    pub fn content_type(&self) -> &Mime {
+        self.field.content_type()
+    }
+
+    /// Filename from the part's content disposition, if any.
+    pub fn filename(&self) -> Option<PathBuf> {
+        get_content_disposition(&self.field)
+            .remove("filename")
+            .map(|s| PathBuf::from(s.to_string()))
+    }
+}
+
+impl Stream for FieldStream {
+    type Item = Result<Bytes, MultipartError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.field).poll_next(cx)
+    }
+}
+
+/// Extractor yielding parts of `T` as soon as each of them is parsed.
+///
+/// Unlike [`MultipartForm`] it doesn't wait for the whole request, so processing can start
+/// while the rest is still uploading. The stream ends after the first error.
+///
+/// Every part is yielded as it comes, so the struct's `duplicate` policies aren't applied and
+/// query string parameters are never read. Required fields aren't checked either, the stream
+/// just ends when the request does. Needs `#[awmpde(stream)]` on `T`.
+pub struct MultipartStream<T: FromMultipartPart> {
+    mp: actix_multipart::Multipart,
+    config: FieldConfig,
+    part: Option<LocalBoxFuture<'static, Result<T::Part, Error>>>,
+    done: bool,
+}
+
+impl<T: FromMultipartPart> MultipartStream<T> {
+    pub fn new(mp: actix_multipart::Multipart, config: FieldConfig) -> Self {
+        Self {
+            mp,
+            config,
+            part: None,
+            done: false,
+        }
+    }
+}
+
+impl<T: FromMultipartPart> Stream for MultipartStream<T> {
+    type Item = Result<T::Part, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        if self.part.is_none() {
+            match ready!(Pin::new(&mut self.mp).poll_next(cx)) {
+                Some(Ok(field)) => self.part = Some(T::from_part(field, &self.config)),
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(actix_web::Error::from(e).into())));
+                }
+                None => {
+                    self.done = true;
+                    return Poll::Ready(None);
+                }
+            }
+        }
+
+        let res = ready!(self.part.as_mut().unwrap().as_mut().poll(cx));
+        self.part = None;
+        self.done = res.is_err();
+        Poll::Ready(Some(res))
+    }
+}
+
+impl<T: FromMultipartPart> FromRequest for MultipartStream<T> {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    #[inline]
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let mp = actix_multipart::Multipart::from_request(req, payload);
+        let config = req.app_data::<FieldConfig>().cloned().unwrap_or_default();
+        async { Ok(Self::new(mp.await?, config)) }.boxed_local()
+    }
+}
@@ -1,15 +1,20 @@
 use std::convert::TryInto;
 
 use actix_multipart::Multipart;
-use actix_utils::mpsc;
 use actix_web::error::PayloadError;
 use actix_web::http::header::{self, HeaderMap};
 use actix_web::web::Bytes;
-use futures::{Stream, StreamExt};
+use futures::Stream;
 
 #[derive(Clone)]
 pub struct MultipartBuilder(Vec<u8>);
 
+impl Default for MultipartBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MultipartBuilder {
     pub const BOUNDARY: &'static str = "--abbc761f78ff4d7cb7573b5a23f96ef0";
     pub const CONTENT_TYPE: &'static str =
@@ -32,14 +37,34 @@ impl MultipartBuilder {
         Self(Default::default()).add_boundary()
     }
 
-    pub fn add_field<'a, C: IntoIterator<Item = u8>>(
-        mut self,
+    pub fn add_field<C: IntoIterator<Item = u8>>(
+        self,
+        name: &'static str,
+        cont_type: Option<&'static str>,
+        content: C,
+    ) -> Self {
+        self.add_part(format!("name=\"{}\"", name), cont_type, content)
+    }
+
+    pub fn add_file<C: IntoIterator<Item = u8>>(
+        self,
         name: &'static str,
+        filename: &'static str,
+        cont_type: Option<&'static str>,
+        content: C,
+    ) -> Self {
+        let params = format!("name=\"{}\"; filename=\"{}\"", name, filename);
+        self.add_part(params, cont_type, content)
+    }
+
+    fn add_part<C: IntoIterator<Item = u8>>(
+        mut self,
+        params: String,
         cont_type: Option<&'static str>,
         content: C,
     ) -> Self {
         self = self
-            .write(format!("Content-Disposition: form-data; name=\"{}\"", name).bytes())
+            .write(format!("Content-Disposition: form-data; {}", params).bytes())
             .new_line();
 
         if let Some(tp) = cont_type {
@@ -55,24 +80,15 @@ impl MultipartBuilder {
     }
 
     pub fn build_payload_bytes(self) -> Bytes {
-        let mut bytes: Bytes = self.0.into();
-        let bytes = bytes.split_to(bytes.len()); // strip crlf
-        bytes
-    }
-
-    fn create_stream() -> (
-        mpsc::Sender<Result<Bytes, PayloadError>>,
-        impl Stream<Item = Result<Bytes, PayloadError>>,
-    ) {
-        let (tx, rx) = mpsc::channel();
-
-        (tx, rx.map(|res| res.map_err(|_| panic!())))
+        // Last boundary closes the body
+        let mut vec = self.0;
+        vec.truncate(vec.len() - 2); // strip crlf
+        vec.extend_from_slice(b"--\r\n");
+        vec.into()
     }
 
     pub fn build_payload(self) -> impl Stream<Item = Result<Bytes, PayloadError>> {
-        let (sender, payload) = Self::create_stream();
-        sender.send(Ok(self.build_payload_bytes())).unwrap();
-        payload
+        futures::stream::once(futures::future::ok(self.build_payload_bytes()))
     }
 
     pub fn build(self) -> Multipart {